@@ -0,0 +1,53 @@
+use std::str::FromStr;
+
+/// Command-line options of the form `--name value` or `--name=value`, and positional arguments.
+/// An option followed by another option or by nothing is a flag with an empty value.
+#[derive(Clone, Debug, Default)]
+pub struct Args {
+	pub positional: Vec<String>,
+	opts: Vec<(String, String)>,
+	/// Printed with the error when a value is invalid.
+	usage: &'static str,
+}
+
+impl Args {
+	pub fn parse<I: Iterator<Item = String>>(it: I) -> Args {
+		let mut args = Args::default();
+		let mut it = it.peekable();
+		while let Some(a) = it.next() {
+			if let Some(a) = a.strip_prefix("--") {
+				if let Some(k) = a.find('=') {
+					args.opts.push((a[..k].to_string(), a[k + 1..].to_string()));
+				} else {
+					let v = if it.peek().is_none_or(|v| v.starts_with("--")) { String::new() } else { it.next().unwrap() };
+					args.opts.push((a.to_string(), v));
+				}
+			} else {
+				args.positional.push(a);
+			}
+		}
+		args
+	}
+	/// The arguments of the program, whose usage is printed if a value is invalid.
+	pub fn from_env(usage: &'static str) -> Args {
+		Args { usage, ..Args::parse(::std::env::args().skip(1)) }
+	}
+	pub fn has(&self, name: &str) -> bool {
+		self.opts.iter().any(|o| o.0 == name)
+	}
+	/// The last value given for `--name`. Exit through invalid if it does not parse.
+	pub fn get<T: FromStr>(&self, name: &str) -> Option<T> {
+		self.opts.iter().rev().find(|o| o.0 == name).map(|o| o.1.parse().unwrap_or_else(|_| self.invalid(name, &o.1)))
+	}
+	/// Print the invalid value of `--name` and the usage, and exit with status 1.
+	pub fn invalid(&self, name: &str, value: &str) -> ! {
+		eprintln!("error: invalid value for --{}: {:?}", name, value);
+		if !self.usage.is_empty() {
+			eprintln!("{}", self.usage);
+		}
+		::std::process::exit(1);
+	}
+	pub fn get_or<T: FromStr>(&self, name: &str, default: T) -> T {
+		self.get(name).unwrap_or(default)
+	}
+}
@@ -0,0 +1,118 @@
+extern crate santa17;
+
+use santa17::*;
+use santa17::args::Args;
+use std::cmp::Ordering;
+
+const USAGE: &str = "usage: diff <old.csv> <new.csv> [--summary]
+
+Lists the children and groups whose gift changed with their effect on child and gift happiness,
+the net effect on both sums and on the score, and the gifts whose fill composition changed.
+With --summary, only the totals and the gifts are printed.";
+
+fn kind(k: usize) -> String {
+	match k {
+		1 => "singles".to_string(),
+		2 => "twins".to_string(),
+		3 => "triplets".to_string(),
+		_ => format!("groups of {}", k),
+	}
+}
+
+/// The gifts of the members, or the common gift if they agree.
+fn gifts(ps: &Vec<usize>, ms: &[usize]) -> String {
+	if ms.iter().all(|&i| ps[i] == ps[ms[0]]) {
+		ps[ms[0]].to_string()
+	} else {
+		ms.iter().map(|&i| ps[i].to_string()).collect::<Vec<_>>().join("/")
+	}
+}
+
+/// Number of children of each unit size given each gift.
+fn composition(spec: &ProblemSpec, groups: &Groups, ps: &Vec<usize>) -> Vec<std::collections::BTreeMap<usize, usize>> {
+	let mut fill = vec![std::collections::BTreeMap::new(); spec.gifts];
+	for u in 0..groups.units() {
+		let ms = groups.unit(u);
+		for &i in ms {
+			if ps[i] < spec.gifts {
+				*fill[ps[i]].entry(ms.len()).or_insert(0) += 1;
+			}
+		}
+	}
+	fill
+}
+
+fn main() {
+	let args = Args::from_env(USAGE);
+	if args.positional.len() != 2 || args.has("help") {
+		eprintln!("{}", USAGE);
+		std::process::exit(1);
+	}
+	let files = Files::from_args(&args);
+	let (spec, g1, g2) = construct_graph(&files).or_exit();
+	let groups = load_groups(&spec, &files).or_exit();
+	let old = read_solution(&args.positional[0]).or_exit();
+	let new = read_solution(&args.positional[1]).or_exit();
+	for (file, ps) in args.positional.iter().zip(&[&old, &new]) {
+		if ps.len() != spec.children {
+			Err::<(), _>(InputError::invalid(file, None, format!("expected {} children, found {}", spec.children, ps.len()))).or_exit();
+		}
+	}
+	let happiness = |ps: &Vec<usize>, i: usize| (g1.get(i, ps[i]).unwrap_or(0), g2.get(i, ps[i]).unwrap_or(0));
+	let mut sums = [(0, 0); 2];
+	for i in 0..spec.children {
+		for (s, ps) in sums.iter_mut().zip(&[&old, &new]) {
+			let (a, b) = happiness(ps, i);
+			s.0 += a;
+			s.1 += b;
+		}
+	}
+	let mut changed = [0; 2];
+	for u in 0..groups.units() {
+		let ms = groups.unit(u);
+		if ms.iter().all(|&i| old[i] == new[i]) { continue }
+		changed[(ms.len() > 1) as usize] += 1;
+		if args.has("summary") { continue }
+		let (mut d1, mut d2) = (0, 0);
+		for &i in ms {
+			let (a, b) = happiness(&old, i);
+			let (c, d) = happiness(&new, i);
+			d1 += c - a;
+			d2 += d - b;
+		}
+		let name = if ms.len() > 1 {
+			format!("group {} ({})", u, ms.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(","))
+		} else {
+			format!("child {}", ms[0])
+		};
+		println!("{}: gift {} -> {}, child {:+}, gift {:+}", name, gifts(&old, ms), gifts(&new, ms), d1, d2);
+	}
+	println!("changed: {} singles, {} groups", changed[0], changed[1]);
+	let (s, t) = (sums[0], sums[1]);
+	println!("child happiness: {} -> {} ({:+})", s.0, t.0, t.0 - s.0);
+	println!("gift happiness: {} -> {} ({:+})", s.1, t.1, t.1 - s.1);
+	let (a, b) = (get_score(&spec, s.0, s.1), get_score(&spec, t.0, t.1));
+	let cmp = match exact_score(&spec, t.0, t.1).cmp(&exact_score(&spec, s.0, s.1)) {
+		Ordering::Greater => "better",
+		Ordering::Less => "worse",
+		Ordering::Equal => "same",
+	};
+	println!("score: {} -> {} ({:+e}, {})", a, b, b - a, cmp);
+	let (f, h) = (composition(&spec, &groups, &old), composition(&spec, &groups, &new));
+	let show = |c: &std::collections::BTreeMap<usize, usize>| c.iter().map(|(&k, &v)| format!("{} {}", v, kind(k))).collect::<Vec<_>>().join(", ");
+	let (mut gained, mut lost) = (vec![0; spec.gifts], vec![0; spec.gifts]);
+	for i in 0..spec.children {
+		if old[i] != new[i] {
+			if new[i] < spec.gifts { gained[new[i]] += 1 }
+			if old[i] < spec.gifts { lost[old[i]] += 1 }
+		}
+	}
+	let mut fills = 0;
+	for j in 0..spec.gifts {
+		if f[j] == h[j] { continue }
+		fills += 1;
+		if args.has("summary") { continue }
+		println!("gift {}: +{} -{}: {} -> {}", j, gained[j], lost[j], show(&f[j]), show(&h[j]));
+	}
+	println!("gifts with changed fill: {}", fills);
+}
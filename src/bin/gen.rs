@@ -0,0 +1,133 @@
+extern crate rand;
+extern crate santa17;
+
+use rand::{Isaac64Rng, Rng, SeedableRng};
+use santa17::*;
+use santa17::args::Args;
+use std::io::Write;
+
+const USAGE: &str = "usage: gen [--out DIR] [--seed S] [--children N] [--gifts M] [--wishlist-len L] [--goodkids-len K]
+           [--groups SIZE:COUNT,...] [--skew S] [--stock-spread F]
+
+Writes child_wishlist_v2.csv, gift_goodkids_v2.csv, spec.txt, groups.csv (and gift_capacity.csv if F > 0) to DIR.
+Gift popularity in the wishlists follows a Zipf law of exponent S (0 for uniform).
+The groups of each SIZE (at least 2) must fit in the N children; by default 0.5% of them are triplets and 4% twins.
+M must be at least 1. Capacities are drawn from [c (1 - F), c (1 + F)] around the average c and sum to at least N.";
+
+/// Draw `len` distinct items, item i with weight w[i], in the order they are drawn (Efraimidis and Spirakis).
+fn weighted_sample<R: Rng>(rng: &mut R, w: &Vec<f64>, len: usize) -> Vec<usize> {
+	let mut keys: Vec<(f64, usize)> = (0..w.len()).map(|i| (rng.next_f64().ln() / w[i], i)).collect();
+	keys.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+	keys.into_iter().take(len).map(|(_, i)| i).collect()
+}
+
+/// Draw `len` distinct items of 0..n uniformly in random order (Floyd).
+fn sample<R: Rng>(rng: &mut R, n: usize, len: usize) -> Vec<usize> {
+	let mut set = std::collections::HashSet::new();
+	let mut list = vec![];
+	for j in n - len..n {
+		let t = rng.gen_range(0, j + 1);
+		let x = if set.contains(&t) { j } else { t };
+		set.insert(x);
+		list.push(x);
+	}
+	rng.shuffle(&mut list);
+	list
+}
+
+fn write_csv(file: &str, rows: &Vec<Vec<usize>>) {
+	let mut writer = std::io::BufWriter::new(std::fs::File::create(file).unwrap());
+	for (i, row) in rows.iter().enumerate() {
+		write!(writer, "{}", i).unwrap();
+		for x in row {
+			write!(writer, ",{}", x).unwrap();
+		}
+		writeln!(writer).unwrap();
+	}
+}
+
+fn main() {
+	let args = Args::from_env(USAGE);
+	if args.positional.len() > 0 || args.has("help") {
+		eprintln!("{}", USAGE);
+		std::process::exit(1);
+	}
+	let out: String = args.get_or("out", ".".to_string());
+	let seed: u64 = args.get_or("seed", 0);
+	let n: usize = args.get_or("children", 10000);
+	let m: usize = args.get_or("gifts", 100);
+	if m == 0 {
+		args.invalid("gifts", "0");
+	}
+	let wishlist_len = args.get_or("wishlist-len", 10usize).min(m);
+	let goodkids_len = args.get_or("goodkids-len", 100usize).min(n);
+	let skew: f64 = args.get_or("skew", 0.0);
+	let spread: f64 = args.get_or("stock-spread", 0.0);
+	let groups: Vec<(usize, usize)> = match args.get::<String>("groups") {
+		Some(s) => {
+			let groups: Vec<(usize, usize)> = s.split(',').filter(|s| !s.is_empty()).map(|s| {
+				let mut kv = s.split(':');
+				match (kv.next().unwrap().parse(), kv.next().map(|c| c.parse())) {
+					(Ok(k), Some(Ok(c))) if k >= 2 => (k, c),
+					_ => args.invalid("groups", s),
+				}
+			}).collect();
+			// The groups must fit in the children.
+			if groups.iter().fold(0usize, |p, &(k, c)| p.saturating_add(k.saturating_mul(c))) > n {
+				args.invalid("groups", &s);
+			}
+			groups
+		}
+		None => vec![(3, (n * 5 / 1000 + 2) / 3), (2, n / 50)],
+	};
+	let mut rng = Isaac64Rng::from_seed(&[seed][..]);
+	let mut rank: Vec<usize> = (0..m).collect();
+	rng.shuffle(&mut rank);
+	let w: Vec<f64> = (0..m).map(|j| 1.0 / ((rank[j] + 1) as f64).powf(skew)).collect();
+	let cs: Vec<Vec<usize>> = (0..n).map(|_| weighted_sample(&mut rng, &w, wishlist_len)).collect();
+	let gs: Vec<Vec<usize>> = (0..m).map(|_| sample(&mut rng, n, goodkids_len)).collect();
+	let mut ids: Vec<usize> = (0..n).collect();
+	rng.shuffle(&mut ids);
+	let mut members = vec![];
+	let mut p = 0;
+	for &(k, c) in &groups {
+		for _ in 0..c {
+			members.push(ids[p..p + k].to_vec());
+			p += k;
+		}
+	}
+	let avg = (n + m - 1) / m;
+	let mut cap = vec![avg; m];
+	if spread > 0.0 {
+		let d = (avg as f64 * spread) as usize;
+		for j in 0..m {
+			cap[j] = rng.gen_range(avg - d.min(avg), avg + d + 1);
+		}
+		let mut j = 0;
+		while cap.iter().sum::<usize>() < n {
+			cap[j % m] += 1;
+			j += 1;
+		}
+	}
+	std::fs::create_dir_all(&out).unwrap();
+	let path = |f: &str| std::path::Path::new(&out).join(f).to_str().unwrap().to_string();
+	write_csv(&path(CHILD_FILE), &cs);
+	write_csv(&path(GIFT_FILE), &gs);
+	let mut writer = std::io::BufWriter::new(std::fs::File::create(path(GROUP_FILE)).unwrap());
+	for ms in &members {
+		writeln!(writer, "{}", ms.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(",")).unwrap();
+	}
+	if spread > 0.0 {
+		let mut writer = std::io::BufWriter::new(std::fs::File::create(path(CAPACITY_FILE)).unwrap());
+		for j in 0..m {
+			writeln!(writer, "{},{}", j, cap[j]).unwrap();
+		}
+	}
+	let mut writer = std::io::BufWriter::new(std::fs::File::create(path(SPEC_FILE)).unwrap());
+	writeln!(writer, "# gen --seed {} --children {} --gifts {} --skew {} --stock-spread {}", seed, n, m, skew, spread).unwrap();
+	writeln!(writer, "children = {}", n).unwrap();
+	writeln!(writer, "gifts = {}", m).unwrap();
+	writeln!(writer, "gift_capacity = {}", avg).unwrap();
+	writeln!(writer, "wishlist_len = {}", wishlist_len).unwrap();
+	writeln!(writer, "goodkids_len = {}", goodkids_len).unwrap();
+}
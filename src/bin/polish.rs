@@ -0,0 +1,153 @@
+extern crate rand;
+extern crate santa17;
+
+use rand::{Isaac64Rng, Rng, SeedableRng};
+use santa17::*;
+use santa17::args::Args;
+
+const USAGE: &str = "usage: polish <solution.csv> [--out FILE] [--seed S] [--time SECONDS] [--patience N]
+
+Improves a feasible solution by swaps that keep every group together and every gift count unchanged:
+units of the same size, a twin with two singles, and a triplet with a twin and a single.
+A swap is kept if it increases the exact score. Stops after SECONDS (default 60) or after N (default 10000000)
+attempts in a row without improvement, and writes the result to FILE (default polished.csv).";
+
+/// The units given each gift, by size, with the position of each unit in its list.
+struct Holders {
+	at: Vec<Vec<Vec<usize>>>,
+	pos: Vec<usize>,
+}
+
+impl Holders {
+	fn new(s: &Scorer) -> Holders {
+		let groups = s.groups;
+		let sizes = (0..groups.units()).map(|u| groups.unit(u).len()).max().unwrap_or(1);
+		let mut h = Holders { at: vec![vec![vec![]; sizes + 1]; s.spec.gifts], pos: vec![0; groups.units()] };
+		for u in 0..groups.units() {
+			h.insert(u, groups.unit(u).len(), s.unit_gift(u));
+		}
+		h
+	}
+	fn insert(&mut self, u: usize, k: usize, j: usize) {
+		self.pos[u] = self.at[j][k].len();
+		self.at[j][k].push(u);
+	}
+	fn remove(&mut self, u: usize, k: usize, j: usize) {
+		let p = self.pos[u];
+		self.at[j][k].swap_remove(p);
+		if p < self.at[j][k].len() {
+			let v = self.at[j][k][p];
+			self.pos[v] = p;
+		}
+	}
+	/// A random unit of size k with gift j other than `except`.
+	fn pick<R: Rng>(&self, rng: &mut R, j: usize, k: usize, except: usize) -> Option<usize> {
+		let list = self.at[j].get(k)?;
+		if list.is_empty() {
+			return None;
+		}
+		let v = list[rng.gen_range(0, list.len())];
+		if v == except { None } else { Some(v) }
+	}
+}
+
+/// A move exchanging the gifts a of unit u and b, completed by units of the other gift so that the counts do not change.
+fn propose<R: Rng>(rng: &mut R, s: &Scorer, h: &Holders, u: usize, b: usize) -> Option<Vec<(usize, usize)>> {
+	let k = s.groups.unit(u).len();
+	let a = s.unit_gift(u);
+	if a == b {
+		return None;
+	}
+	Some(match (k, rng.gen_range(0, 3)) {
+		(_, 0) => vec![(u, b), (h.pick(rng, b, k, u)?, a)],
+		(1, 1) => vec![(u, b), (h.pick(rng, a, 1, u)?, b), (h.pick(rng, b, 2, u)?, a)],
+		(1, _) => vec![(u, b), (h.pick(rng, a, 2, u)?, b), (h.pick(rng, b, 3, u)?, a)],
+		(2, 1) => {
+			let (v, w) = (h.pick(rng, b, 1, u)?, h.pick(rng, b, 1, u)?);
+			if v == w {
+				return None;
+			}
+			vec![(u, b), (v, a), (w, a)]
+		}
+		(2, _) => vec![(u, b), (h.pick(rng, a, 1, u)?, b), (h.pick(rng, b, 3, u)?, a)],
+		(3, _) => vec![(u, b), (h.pick(rng, b, 2, u)?, a), (h.pick(rng, b, 1, u)?, a)],
+		_ => return None,
+	})
+}
+
+/// A gift on the list of a random member of u or listing it, and sometimes a uniformly random gift.
+fn target<R: Rng>(rng: &mut R, s: &Scorer, g1: &PreferenceGraph, g2: &PreferenceGraph, u: usize) -> usize {
+	let ms = s.groups.unit(u);
+	let i = ms[rng.gen_range(0, ms.len())];
+	let (r1, r2) = (g1.row(i), g2.row(i));
+	let n = r1.len() + r2.len();
+	let k = rng.gen_range(0, n + n / 10 + 1);
+	if k < r1.len() {
+		g1.row(i).nth(k).unwrap().0
+	} else if k < n {
+		g2.row(i).nth(k - r1.len()).unwrap().0
+	} else {
+		rng.gen_range(0, s.spec.gifts)
+	}
+}
+
+fn main() {
+	let args = Args::from_env(USAGE);
+	if args.positional.len() != 1 || args.has("help") {
+		eprintln!("{}", USAGE);
+		std::process::exit(1);
+	}
+	let out: String = args.get_or("out", "polished.csv".to_string());
+	let seed: u64 = args.get_or("seed", 0);
+	let time: f64 = args.get_or("time", 60.0);
+	let patience: usize = args.get_or("patience", 10000000);
+	let files = Files::from_args(&args);
+	let (spec, g1, g2) = construct_graph(&files).or_exit();
+	let groups = load_groups(&spec, &files).or_exit();
+	let cap = load_capacities(&spec, &files).or_exit();
+	let ps = read_solution(&args.positional[0]).or_exit();
+	let rows = ps.iter().enumerate().map(|(i, &j)| (i + 2, i, j)).collect();
+	let (report, _) = validate::validate(&spec, &groups, &cap, &rows);
+	if !report.is_ok() {
+		println!("{}", report);
+		std::process::exit(2);
+	}
+	let mut s = Scorer::new(&spec, &groups, &g1, &g2, ps);
+	let mut h = Holders::new(&s);
+	let mut rng = Isaac64Rng::from_seed(&[seed][..]);
+	let start = std::time::Instant::now();
+	let initial = s.score();
+	eprintln!("score = {}", initial);
+	let (mut tries, mut idle, mut moves) = (0usize, 0, 0);
+	while idle < patience {
+		tries += 1;
+		if tries % 100000 == 0 {
+			if start.elapsed().as_secs_f64() > time { break }
+			if tries % 10000000 == 0 {
+				eprintln!("time = {:.1}, tries = {}, moves = {}, score = {}", start.elapsed().as_secs_f64(), tries, moves, s.score());
+			}
+		}
+		idle += 1;
+		let u = rng.gen_range(0, groups.units());
+		let b = target(&mut rng, &s, &g1, &g2, u);
+		let mv = match propose(&mut rng, &s, &h, u, b) {
+			Some(mv) => mv,
+			None => continue,
+		};
+		if !s.improves(s.delta(&mv)) { continue }
+		for &(v, j) in &mv {
+			let k = groups.unit(v).len();
+			h.remove(v, k, s.unit_gift(v));
+			h.insert(v, k, j);
+		}
+		s.apply(&mv);
+		moves += 1;
+		idle = 0;
+	}
+	let (score1, score2) = s.sums();
+	eprintln!("time = {:.1}, tries = {}, moves = {}", start.elapsed().as_secs_f64(), tries, moves);
+	println!("{} {}", score1, score2);
+	println!("{} -> {}", initial, s.score());
+	println!("{}", s.exact());
+	write_solution(s.assignment(), &out);
+}
@@ -0,0 +1,37 @@
+extern crate santa17;
+
+use santa17::*;
+use santa17::args::Args;
+
+const USAGE: &str = "usage: report <solution.csv> [--format text|csv|json]
+
+Prints how the children did by the size of their group: the histograms of the rank of each child's gift
+in its wishlist and of the child in the list of its gift (with \"none\" for not on the list), and the mean happiness.";
+
+fn main() {
+	let args = Args::from_env(USAGE);
+	if args.positional.len() != 1 || args.has("help") {
+		eprintln!("{}", USAGE);
+		std::process::exit(1);
+	}
+	let files = Files::from_args(&args);
+	let (spec, g1, g2) = construct_graph(&files).or_exit();
+	let groups = load_groups(&spec, &files).or_exit();
+	let rows = read_solution_rows(&args.positional[0]).or_exit();
+	let mut ps = vec![!0; spec.children];
+	for &(_, i, j) in rows.iter().rev() {
+		if i < spec.children {
+			ps[i] = j;
+		}
+	}
+	let d = distribution::distribution(&spec, &groups, &g1, &g2, &ps);
+	match args.get_or("format", "text".to_string()).as_str() {
+		"text" => print!("{}", d),
+		"csv" => print!("{}", d.to_csv()),
+		"json" => println!("{}", d.to_json()),
+		_ => {
+			eprintln!("{}", USAGE);
+			std::process::exit(1);
+		}
+	}
+}
@@ -1,57 +1,37 @@
 extern crate santa17;
 
 use santa17::*;
+use santa17::args::Args;
+
+const USAGE: &str = "usage: score <solution.csv> [--dir DIR] [--child FILE] [--gift FILE] [--spec FILE] [--groups FILE] [--capacity FILE] [--cache FILE]
+
+Prints the validation report, the two sums of weights and the score, counting the unassigned children as not on any list.
+Exits with status 2 if the submission is invalid.";
 
 fn main() {
-	let (g1, g2) = construct_graph();
-	let ps = read_solution(&std::env::args().nth(1).unwrap());
-	let mut count = vec![0; N];
-	let mut err = false;
-	for i in 0..N {
-		if ps[i] == !0 {
-			err = true;
-		} else {
-			count[ps[i]] += 1;
-		}
-	}
-	if err {
-		println!("mismatch!");
-	}
-	err = false;
-	for j in 0..N {
-		assert!(count[j] <= 1000);
-	}
-	for i in 0..N3 {
-		if ps[i * 3] != ps[i * 3 + 1] || ps[i * 3] != ps[i * 3 + 2] {
-			err = true;
-		}
-	}
-	if err {
-		println!("triple!");
-	}
-	err = false;
-	for i in 0..N2 {
-		if ps[N3 * 3 + i * 2] != ps[N3 * 3 + i * 2 + 1] {
-			err = true;
-		}
-	}
-	if err {
-		println!("twin!");
-	}
+	let args = Args::from_env(USAGE);
+	if args.positional.len() != 1 || args.has("help") {
+		eprintln!("{}", USAGE);
+		std::process::exit(1);
+	}
+	let file = &args.positional[0];
+	let files = Files::from_args(&args);
+	let (spec, g1, g2) = construct_graph(&files).or_exit();
+	let groups = load_groups(&spec, &files).or_exit();
+	let cap = load_capacities(&spec, &files).or_exit();
+	let rows = read_solution_rows(file).or_exit();
+	let (report, ps) = validate::validate(&spec, &groups, &cap, &rows);
+	println!("{}", report);
 	let mut score1 = 0;
 	let mut score2 = 0;
-	for i in 0..N {
-		for &(j, w) in &g1[i] {
-			if j == ps[i] {
-				score1 += w;
-			}
-		}
-		for &(j, w) in &g2[i] {
-			if j == ps[i] {
-				score2 += w;
-			}
-		}
+	for i in 0..spec.children {
+		score1 += g1.get(i, ps[i]).unwrap_or(0);
+		score2 += g2.get(i, ps[i]).unwrap_or(0);
 	}
 	println!("{} {}", score1, score2);
-	println!("{}", get_score(score1, score2));
+	println!("{}", get_score(&spec, score1, score2));
+	println!("{}", exact_score(&spec, score1, score2));
+	if !report.is_ok() {
+		std::process::exit(2);
+	}
 }
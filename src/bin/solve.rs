@@ -3,35 +3,53 @@
 extern crate santa17;
 
 use santa17::*;
+use santa17::args::Args;
+use std::collections::BinaryHeap;
+use std::sync::{Condvar, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-fn modify(ps: &Vec<usize>) -> Vec<usize> {
-	let mut qs = vec![];
-	for i in 0..N3 {
-		for _ in 0..3 {
-			qs.push(ps[i]);
-		}
-	}
-	for i in 0..N2 {
-		for _ in 0..2 {
-			qs.push(ps[N3 + i]);
-		}
-	}
-	for i in N3 + N2..ps.len() {
-		qs.push(ps[i]);
-	}
-	qs
-}
+const USAGE: &str = "usage: solve [--dir DIR] [--child FILE] [--gift FILE] [--spec FILE] [--groups FILE] [--capacity FILE] [--cache FILE]
+             [--out FILE] [--init FILE | --lb VALUE] [--time SECONDS] [--nodes N] [--stop-file FILE] [--phase relax|reduce|full]
+             [--node-selection dfs|best|hybrid] [--branching strong|top|pseudocost|reliability] [--candidates K] [--reliability R]
+             [--heuristics F] [--threads N] [--checkpoint FILE [--checkpoint-interval SECONDS] [--resume]]
+             [--verbose LEVEL] [--log FILE] [--log-format text|json]
+
+Reads the instance from DIR (default .), where each file can be given separately, and writes the best solution found to FILE (default out.csv).
+--init starts from the solution in FILE and --lb from the objective VALUE (l (child sum * 2^64 + gift sum) for the lcm l of the group sizes);
+only strictly better solutions are searched for.
+The search stops after SECONDS or N nodes, or once FILE exists, and reports the incumbent, the best bound of the open nodes and the gap.
+With --checkpoint, the state of the search is saved to FILE every SECONDS (default 600) and when it stops, and removed when it finishes;
+--resume continues the search saved in FILE.
+The open nodes are visited depth-first (dfs, default), by largest bound (best), or by dives to a leaf followed by
+a jump to the largest bound (hybrid); the last two tighten the bound of the open nodes faster when the search is stopped.
+The group and gift to branch on are chosen by strong branching on every fractional group (strong, default) or on the K
+(default 8) closest to half assigned (top), by the pseudo-costs learned from the earlier strong branchings (pseudocost),
+or by strong branching on the groups whose pseudo-costs have been learned fewer than R (default 4) times (reliability).
+At the first node and then every F nodes (default 100, 0 for never), a rounding of the relaxation and a dive fixing
+its most assigned fractional group until none is left look for better solutions.
+With N threads (default 1, 0 for one per core), each thread searches its own subtrees and gives an open node to the
+threads waiting for one; the search with one thread is deterministic.
+The relax phase only solves the relaxation, and reduce also fixes the assignments that cannot be improved.
+Progress events go to FILE (default stderr) as key=value or JSON lines. LEVEL is 0 (results only), 1 (progress, default),
+2 (every node) or 3 (flow solver).";
 
+/// Assign the units with ps[i] == !0 to the residual capacities.
+/// Groups are packed by a DP over the gifts whose state is the number of groups of each size used so far,
+/// and then the singles fill what is left.
 fn packing(mut ps: Vec<usize>, mul: &Vec<i32>, cap: &Vec<i32>) -> Option<Vec<usize>> {
 	let n = mul.len();
 	let m = cap.len();
 	let mut count = vec![0; m];
-	let mut nums = vec![vec![]; 4];
+	let mut nums = vec![vec![]; 2];
 	for i in 0..n {
 		if ps[i] != !0 {
 			count[ps[i]] += mul[i];
 		} else {
-			nums[mul[i] as usize].push(i);
+			let k = mul[i] as usize;
+			if nums.len() <= k {
+				nums.resize(k + 1, vec![]);
+			}
+			nums[k].push(i);
 		}
 	}
 	let mut rem = vec![];
@@ -40,44 +58,55 @@ fn packing(mut ps: Vec<usize>, mul: &Vec<i32>, cap: &Vec<i32>) -> Option<Vec<usi
 			rem.push(i);
 		}
 	}
-	let n2 = nums[2].len();
-	let n3 = nums[3].len();
-	eprintln!("{} {} {}", rem.len(), n2, n3);
-	let mut dp = mat![!0; rem.len() + 1; n2 + 1; n3 + 1];
-	dp[0][0][0] = !1;
+	let sizes: Vec<usize> = (2..nums.len()).filter(|&k| nums[k].len() > 0).collect();
+	let cnt: Vec<usize> = sizes.iter().map(|&k| nums[k].len()).collect();
+	let mut stride = vec![1; sizes.len() + 1];
+	for t in 0..sizes.len() {
+		stride[t + 1] = stride[t] * (cnt[t] + 1);
+	}
+	let full = stride[sizes.len()] - 1;
+	event!(2, "packing", gifts = rem.len(), sizes = format!("{:?}", sizes), counts = format!("{:?}", cnt));
+	let digit = |s: usize, t: usize| s / stride[t] % (cnt[t] + 1);
+	// dp[i + 1][s] = the state before packing rem[i].
+	let mut dp = mat![!0; rem.len() + 1; full + 1];
+	dp[0][0] = !1;
+	let mut stack = vec![];
 	for i in 0..rem.len() {
-		for a in 0..n2+1 {
-			for b in 0..n3+1 {
-				if dp[i][a][b] != !0 {
-					for j in 0..n2-a+1 {
-						if j as i32 * 2 <= cap[rem[i]] - count[rem[i]] && (cap[rem[i]] - count[rem[i]] - j as i32 * 2) % 3 == 0 && b as i32 + (cap[rem[i]] - count[rem[i]] - j as i32 * 2) / 3 <= n3 as i32 {
-							let a2 = a + j;
-							let b2 = b as i32 + (cap[rem[i]] - count[rem[i]] - j as i32 * 2) / 3;
-							dp[i + 1][a2][b2 as usize] = j;
-						}
+		let room = cap[rem[i]] - count[rem[i]];
+		for s in 0..full + 1 {
+			if dp[i][s] == !0 { continue }
+			stack.push((0, s, room));
+			while let Some((t, s2, left)) = stack.pop() {
+				if t == sizes.len() {
+					if dp[i + 1][s2] == !0 {
+						dp[i + 1][s2] = s;
 					}
+					continue;
+				}
+				let k = sizes[t];
+				let mut c = 0;
+				while digit(s2, t) + c <= cnt[t] && (c * k) as i32 <= left {
+					stack.push((t + 1, s2 + c * stride[t], left - (c * k) as i32));
+					c += 1;
 				}
 			}
 		}
 	}
-	if dp[rem.len()][n2][n3] == !0 {
+	if dp[rem.len()][full] == !0 {
 		return None;
 	}
-	let mut a = n2;
-	let mut b = n3;
+	let mut s = full;
 	for i in (0..rem.len()).rev() {
-		let j2 = dp[i + 1][a][b];
-		let j3 = (cap[rem[i]] - count[rem[i]] - j2 as i32 * 2) / 3;
-		for _ in 0..j2 {
-			let x = nums[2].pop().unwrap();
-			ps[x] = rem[i];
-		}
-		for _ in 0..j3 {
-			let x = nums[3].pop().unwrap();
-			ps[x] = rem[i];
+		let s2 = dp[i + 1][s];
+		for t in 0..sizes.len() {
+			let k = sizes[t];
+			for _ in 0..digit(s, t) - digit(s2, t) {
+				let x = nums[k].pop().unwrap();
+				ps[x] = rem[i];
+				count[rem[i]] += k as i32;
+			}
 		}
-		a -= j2;
-		b -= j3 as usize;
+		s = s2;
 	}
 	for i in 0..n {
 		if ps[i] != !0 { continue }
@@ -94,186 +123,1120 @@ fn packing(mut ps: Vec<usize>, mul: &Vec<i32>, cap: &Vec<i32>) -> Option<Vec<usi
 	Some(ps)
 }
 
-fn choose(data: &Data, g: &mut mincostcirculation::Graph<i32, i128>) -> ((usize, usize), (i128, i128)) {
+/// A fractional assignment of a group in the relaxation: the edge g.es[unit][edge] to gift `gift` carries the fraction `frac` of the group.
+#[derive(Clone, Copy, Debug)]
+struct Candidate {
+	unit: usize,
+	gift: usize,
+	edge: usize,
+	frac: f64,
+}
+
+/// The fractional assignments of groups in the current relaxation, by unit and then by edge.
+fn candidates(data: &Data, g: &mincostcirculation::Graph<i32, i128>) -> Vec<Candidate> {
 	let n = data.mul.len();
-	let m = data.cap.len();
-	let r = n + m;
-	let mut scores = (data.score + 1, data.score + 1);
-	let mut score = data.score;
-	let mut t = (!0, !0);
+	let r = n + data.cap.len();
+	let mut cs = vec![];
 	for i in 0..n {
 		if data.mul[i] == 1 { continue }
-		for j in 0..g.es[i].len() {
-			let e = g.es[i][j];
+		for (k, e) in g.es[i].iter().enumerate() {
 			if n <= e.to && e.to < r && 0 < e.cap && e.cap < data.mul[i] {
-				eprintln!("try: {} -> {}", i, e.to - n);
-				let mut tmp = (0, 0);
-				for _ in 0..data.mul[i] {
-					score -= e.cost;
-					score -= g.dec(r, i);
-					score -= g.dec(e.to, r);
-				}
-				tmp.0 = score;
-				for _ in 0..data.mul[i] {
-					score += e.cost;
-					score -= g.dec(i, e.to);
-					score -= g.inc(r, i);
-					score -= g.inc(e.to, r);
-				}
-				tmp.1 = score;
-				for _ in 0..data.mul[i] {
-					score -= g.inc(i, e.to);
-				}
-				eprintln!("{:?}", tmp);
-				if tmp.0 <= data.lb || tmp.1 <= data.lb {
-					scores = tmp;
-					t = (i, e.to - n);
-					break;
-				} else if scores.0.max(scores.1) > tmp.0.max(tmp.1)
-						|| scores.0.max(scores.1) == tmp.0.max(tmp.1) && scores.0 + scores.1 > tmp.0 + tmp.1 {
-					scores = tmp;
-					t = (i, e.to - n);
-				}
+				cs.push(Candidate { unit: i, gift: e.to - n, edge: k, frac: (data.mul[i] - e.cap) as f64 / data.mul[i] as f64 });
 			}
 		}
 	}
-	(t, scores)
+	cs
 }
 
-fn rec(data: &mut Data, g: &mut mincostcirculation::Graph<i32, i128>, dir: &str) {
+/// The bounds of fixing and of forbidding the assignment of c by strong branching,
+/// which leaves the relaxation at an optimum of the same value.
+/// The group cannot be fixed to a gift with less room than its size, which gets the fix bound i128::min_value().
+fn strong(data: &Data, g: &mut mincostcirculation::Graph<i32, i128>, c: &Candidate) -> (i128, i128) {
 	let n = data.mul.len();
-	let m = data.cap.len();
-	let r = n + m;
-	eprintln!("{}: score: {} ({})", dir, data.score, data.lb);
-	let ((i, j), scores) = choose(data, g);
-	if i != !0 {
-		eprintln!("{:?}", scores);
-		if scores.0 <= data.lb && scores.1 <= data.lb {
-			return;
+	let r = n + data.cap.len();
+	let i = c.unit;
+	let e = g.es[i][c.edge];
+	let mut score = data.score;
+	let mut tmp = (0, 0);
+	if room(data, g, c.gift) < data.mul[i] {
+		for _ in 0..data.mul[i] {
+			score -= g.dec(i, e.to);
+		}
+		tmp = (i128::min_value(), score);
+		for _ in 0..data.mul[i] {
+			g.inc(i, e.to);
 		}
-		let cost = get_cost(&data.a[i], j).unwrap();
-		if scores.0 >= scores.1 {
-			if scores.1 <= data.lb {
-				eprintln!("{}: reduce: {} = {}", dir, i, j);
+		event!(2, "try", unit = data.id[i], gift = c.gift, bound_eq = None::<i128>, bound_ne = tmp.1);
+		return tmp;
+	}
+	for _ in 0..data.mul[i] {
+		score -= e.cost;
+		score -= g.dec(r, i);
+		score -= g.dec(e.to, r);
+	}
+	tmp.0 = score;
+	for _ in 0..data.mul[i] {
+		score += e.cost;
+		score -= g.dec(i, e.to);
+		score -= g.inc(r, i);
+		score -= g.inc(e.to, r);
+	}
+	tmp.1 = score;
+	for _ in 0..data.mul[i] {
+		g.inc(i, e.to);
+	}
+	event!(2, "try", unit = data.id[i], gift = c.gift, bound_eq = tmp.0, bound_ne = tmp.1);
+	tmp
+}
+
+/// Whether branching with the bounds a is better than with b: the larger bound is smaller, or equal with a smaller sum.
+fn better(a: (i128, i128), b: (i128, i128)) -> bool {
+	b.0.max(b.1) > a.0.max(a.1) || b.0.max(b.1) == a.0.max(a.1) && b.0 + b.1 > a.0 + a.1
+}
+
+/// How to pick the fractional group and gift to branch on at a node.
+trait BranchingRule {
+	/// The unit and gift to branch on with the bounds of fixing and forbidding, or (!0, !0) if no group is fractional.
+	/// The bounds are those of the relaxation; a candidate with a bound not above the incumbent may be taken at once.
+	fn choose(&mut self, data: &Data, g: &mut mincostcirculation::Graph<i32, i128>) -> ((usize, usize), (i128, i128));
+}
+
+/// Strong branching on every fractional assignment.
+struct FullStrong;
+
+impl BranchingRule for FullStrong {
+	fn choose(&mut self, data: &Data, g: &mut mincostcirculation::Graph<i32, i128>) -> ((usize, usize), (i128, i128)) {
+		let n = data.mul.len();
+		let r = n + data.cap.len();
+		let mut scores = (data.score + 1, data.score + 1);
+		let mut t = (!0, !0);
+		for i in 0..n {
+			if data.mul[i] == 1 { continue }
+			// The flow changes with each evaluation, so the fractional edges are found as they come.
+			for k in 0..g.es[i].len() {
+				let e = g.es[i][k];
+				if n <= e.to && e.to < r && 0 < e.cap && e.cap < data.mul[i] {
+					let c = Candidate { unit: i, gift: e.to - n, edge: k, frac: 0.0 };
+					let tmp = strong(data, g, &c);
+					if tmp.0 <= data.lb || tmp.1 <= data.lb {
+						scores = tmp;
+						t = (i, c.gift);
+						break;
+					} else if better(tmp, scores) {
+						scores = tmp;
+						t = (i, c.gift);
+					}
+				}
+			}
+		}
+		(t, scores)
+	}
+}
+
+/// Strong branching on the k most fractional assignments, those whose fraction is the closest to one half.
+struct TopStrong {
+	k: usize,
+}
+
+impl BranchingRule for TopStrong {
+	fn choose(&mut self, data: &Data, g: &mut mincostcirculation::Graph<i32, i128>) -> ((usize, usize), (i128, i128)) {
+		let mut cs = candidates(data, g);
+		cs.sort_by(|a, b| (a.frac - 0.5).abs().partial_cmp(&(b.frac - 0.5).abs()).unwrap());
+		let mut scores = (data.score + 1, data.score + 1);
+		let mut t = (!0, !0);
+		for c in cs.iter().take(self.k) {
+			let tmp = strong(data, g, c);
+			if tmp.0 <= data.lb || tmp.1 <= data.lb {
+				return ((c.unit, c.gift), tmp);
+			} else if better(tmp, scores) {
+				scores = tmp;
+				t = (c.unit, c.gift);
+			}
+		}
+		(t, scores)
+	}
+}
+
+/// The sum and number of the costs learned for each unit, and over all units.
+struct Costs {
+	unit: Vec<(f64, usize)>,
+	total: (f64, usize),
+}
+
+impl Costs {
+	fn new(n: usize) -> Costs {
+		Costs { unit: vec![(0.0, 0); n], total: (0.0, 0) }
+	}
+	fn add(&mut self, i: usize, cost: f64) {
+		self.unit[i].0 += cost;
+		self.unit[i].1 += 1;
+		self.total.0 += cost;
+		self.total.1 += 1;
+	}
+	/// The mean of unit i, or of every unit if it has not been learned yet.
+	fn mean(&self, i: usize) -> f64 {
+		let (s, k) = if self.unit[i].1 > 0 { self.unit[i] } else { self.total };
+		if k > 0 { s / k as f64 } else { 0.0 }
+	}
+}
+
+/// Pseudo-costs: the mean decrease of the bound per unit of fraction, learned from strong branching,
+/// when a unit is fixed to a gift and when it is forbidden one.
+struct PseudoCost {
+	fix: Costs,
+	forbid: Costs,
+}
+
+impl PseudoCost {
+	fn new(n: usize) -> PseudoCost {
+		PseudoCost { fix: Costs::new(n), forbid: Costs::new(n) }
+	}
+	fn learn(&mut self, data: &Data, c: &Candidate, bounds: (i128, i128)) {
+		// A fix without room tells nothing about the decrease of the bound.
+		if bounds.0 != i128::min_value() {
+			self.fix.add(c.unit, (data.score - bounds.0) as f64 / (1.0 - c.frac));
+		}
+		self.forbid.add(c.unit, (data.score - bounds.1) as f64 / c.frac);
+	}
+	fn estimate(&self, data: &Data, c: &Candidate) -> (i128, i128) {
+		let d0 = self.fix.mean(c.unit) * (1.0 - c.frac);
+		let d1 = self.forbid.mean(c.unit) * c.frac;
+		(data.score - d0 as i128, data.score - d1 as i128)
+	}
+	/// Whether both pseudo-costs of unit i have been learned at least r times.
+	fn reliable(&self, i: usize, r: usize) -> bool {
+		self.fix.unit[i].1.min(self.forbid.unit[i].1) >= r
+	}
+}
+
+impl BranchingRule for PseudoCost {
+	/// The candidate with the best estimated bounds, whose actual bounds are then learned.
+	fn choose(&mut self, data: &Data, g: &mut mincostcirculation::Graph<i32, i128>) -> ((usize, usize), (i128, i128)) {
+		let cs = candidates(data, g);
+		let mut best: Option<(Candidate, (i128, i128))> = None;
+		for c in cs {
+			let est = self.estimate(data, &c);
+			if best.map_or(true, |b| better(est, b.1)) {
+				best = Some((c, est));
+			}
+		}
+		match best {
+			Some((c, _)) => {
+				let bounds = strong(data, g, &c);
+				self.learn(data, &c, bounds);
+				((c.unit, c.gift), bounds)
+			}
+			None => ((!0, !0), (data.score + 1, data.score + 1)),
+		}
+	}
+}
+
+/// Pseudo-cost branching where the candidates of units whose pseudo-costs have been learned fewer than `reliability` times
+/// are evaluated by strong branching instead, in the order of their estimates.
+struct Reliability {
+	costs: PseudoCost,
+	reliability: usize,
+}
+
+impl BranchingRule for Reliability {
+	fn choose(&mut self, data: &Data, g: &mut mincostcirculation::Graph<i32, i128>) -> ((usize, usize), (i128, i128)) {
+		let mut cs: Vec<(Candidate, (i128, i128))> = candidates(data, g).into_iter().map(|c| (c, self.costs.estimate(data, &c))).collect();
+		cs.sort_by(|a, b| if better(a.1, b.1) { std::cmp::Ordering::Less } else if better(b.1, a.1) { std::cmp::Ordering::Greater } else { std::cmp::Ordering::Equal });
+		// The best candidate so far, with whether its bounds are exact.
+		let mut best: Option<(Candidate, (i128, i128), bool)> = None;
+		for (c, est) in cs {
+			let (bounds, exact) = if self.costs.reliable(c.unit, self.reliability) {
+				(est, false)
 			} else {
-				eprintln!("{}: branch: {} = {}", dir, i, j);
+				let bounds = strong(data, g, &c);
+				self.costs.learn(data, &c, bounds);
+				if bounds.0 <= data.lb || bounds.1 <= data.lb {
+					return ((c.unit, c.gift), bounds);
+				}
+				(bounds, true)
+			};
+			if best.map_or(true, |b| better(bounds, b.1)) {
+				best = Some((c, bounds, exact));
+			}
+		}
+		match best {
+			Some((c, bounds, true)) => ((c.unit, c.gift), bounds),
+			Some((c, _, false)) => {
+				let bounds = strong(data, g, &c);
+				self.costs.learn(data, &c, bounds);
+				((c.unit, c.gift), bounds)
 			}
-			for _ in 0..data.mul[i] {
+			None => ((!0, !0), (data.score + 1, data.score + 1)),
+		}
+	}
+}
+
+/// A branching decision: unit `unit` gets gift `gift` (fix) or not (!fix), with `bound` the relaxation once it is taken.
+/// `other` is the bound of the opposite decision if it remains to be explored, and `second` tells whether this is the opposite decision.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Decision {
+	unit: usize,
+	gift: usize,
+	fix: bool,
+	bound: i128,
+	other: Option<i128>,
+	second: bool,
+}
+
+impl Decision {
+	fn tag(&self) -> &'static str {
+		if self.second { "-" } else if self.other.is_some() { "+" } else { "" }
+	}
+	fn opposite(&self, bound: i128) -> Decision {
+		Decision { fix: !self.fix, bound, other: None, second: true, ..*self }
+	}
+}
+
+/// Take (on) or undo (!on) the decision d in the relaxation.
+fn set(data: &mut Data, g: &mut mincostcirculation::Graph<i32, i128>, d: &Decision, on: bool) {
+	let n = data.mul.len();
+	let r = n + data.cap.len();
+	let (i, j) = (d.unit, d.gift);
+	let cost = get_cost(&data.a[i], j).unwrap();
+	for _ in 0..data.mul[i] {
+		match (d.fix, on) {
+			(true, true) => {
 				data.score += cost;
-				data.fixed[data.id[i]] = j;
 				data.score -= g.dec(r, i);
 				data.score -= g.dec(n + j, r);
 			}
-			assert_eq!(data.score, scores.0);
-			if data.score > data.lb {
-				let dir2 = if scores.1 <= data.lb { dir.to_string() } else { dir.to_string() + "+" };
-				rec(data, g, &dir2);
-			}
-			if scores.1 > data.lb {
-				eprintln!("{}: branch: {} != {}", dir, i, j);
-				for _ in 0..data.mul[i] {
-					data.score -= cost;
-					data.fixed[data.id[i]] = !0;
-					data.score -= g.dec(i, n + j);
-					data.score -= g.inc(r, i);
-					data.score -= g.inc(n + j, r);
-				}
-				assert_eq!(data.score, scores.1);
-				if data.score > data.lb {
-					rec(data, g, &(dir.to_string() + "-"));
-				}
-				for _ in 0..data.mul[i] {
-					data.score -= g.inc(i, n + j);
-				}
-			} else {
-				for _ in 0..data.mul[i] {
-					data.score -= cost;
-					data.fixed[data.id[i]] = !0;
-					data.score -= g.inc(r, i);
-					data.score -= g.inc(n + j, r);
-				}
+			(true, false) => {
+				data.score -= cost;
+				data.score -= g.inc(r, i);
+				data.score -= g.inc(n + j, r);
 			}
-		} else {
-			if scores.0 <= data.lb {
-				eprintln!("{}: reduce: {} != {}", dir, i, j);
-			} else {
-				eprintln!("{}: branch: {} != {}", dir, j, j);
-			}
-			for _ in 0..data.mul[i] {
-				data.score -= g.dec(i, n + j);
-			}
-			assert_eq!(data.score, scores.1);
-			if data.score > data.lb {
-				let dir2 = if scores.0 <= data.lb { dir.to_string() } else { dir.to_string() + "+" };
-				rec(data, g, &dir2);
-			}
-			if scores.0 > data.lb {
-				eprintln!("{}: branch: {} = {}", dir, i, j);
-				for _ in 0..data.mul[i] {
-					data.score += cost;
-					data.fixed[i] = j;
-					data.score -= g.dec(r, i);
-					data.score -= g.dec(n + j, r);
-				}
-				assert_eq!(data.score, scores.0);
-				if data.score > data.lb {
-					rec(data, g, &(dir.to_string() + "-"));
+			(false, true) => data.score -= g.dec(i, n + j),
+			(false, false) => data.score -= g.inc(i, n + j),
+		}
+	}
+	if d.fix {
+		data.fixed[data.id[i]] = if on { j } else { !0 };
+	}
+}
+
+/// Evaluate the node at the end of data.path, and return the decision to branch on, if any.
+/// A node without fractional group is turned into a solution by packing.
+/// If the search has stopped, the node is left open and data.halted is set.
+fn visit(groups: &Groups, data: &mut Data, g: &mut mincostcirculation::Graph<i32, i128>, rule: &mut dyn BranchingRule) -> Option<Decision> {
+	data.sync();
+	if data.stopped() {
+		data.halted = true;
+		return None;
+	}
+	data.lb = data.lb.max(data.shared.incumbent.lock().unwrap().0);
+	data.share();
+	let k = data.shared.nodes.fetch_add(1, Ordering::Relaxed);
+	event!(2, "node", worker = data.worker, path = data.dir(), depth = data.path.len(), bound = data.score, incumbent = data.lb);
+	let ((i, j), scores) = rule.choose(data, g);
+	if i != !0 {
+		let f = data.config.heuristics;
+		if f > 0 && k % f == 0 {
+			rounding(groups, data, g);
+			dive(groups, data, g);
+		}
+		if scores.0 <= data.lb && scores.1 <= data.lb {
+			return None;
+		}
+		// Take the decision with the larger bound first.
+		let fix = scores.0 >= scores.1;
+		let (b1, b2) = if fix { scores } else { (scores.1, scores.0) };
+		let kind = if b2 <= data.lb { "reduce" } else { "branch" };
+		event!(2, kind, worker = data.worker, path = data.dir(), depth = data.path.len(), unit = data.id[i], gift = j, fix = fix, bound = b1);
+		let other = if b2 > data.lb { Some(b2) } else { None };
+		return Some(Decision { unit: i, gift: j, fix, bound: b1, other, second: false });
+	}
+	let sub = relaxed_assignment(data, g);
+	if let Some(ps) = complete(data, sub) {
+		let value = data.score;
+		offer(groups, data, &ps, value, "leaf");
+	} else {
+		event!(2, "packing_failed", worker = data.worker, path = data.dir(), depth = data.path.len(), bound = data.score);
+	}
+	None
+}
+
+/// The gift of each reduced unit in the relaxation: the gift it is fixed to, or else the last gift with flow from it, or !0.
+fn relaxed_assignment(data: &Data, g: &mincostcirculation::Graph<i32, i128>) -> Vec<usize> {
+	let n = data.mul.len();
+	let r = n + data.cap.len();
+	let mut sub = vec![!0; n];
+	for i in 0..n {
+		sub[i] = data.fixed[data.id[i]];
+		for e in &g.es[i] {
+			if n <= e.to && e.to < r && g.es[e.to][e.rev].cap > 0 {
+				sub[i] = e.to - n;
+			}
+		}
+	}
+	sub
+}
+
+/// The assignment of every unit, with the reduced units given sub and packed where sub is !0, if packing succeeds.
+fn complete(data: &Data, sub: Vec<usize>) -> Option<Vec<usize>> {
+	let qs = packing(sub, &data.mul, &data.cap)?;
+	let mut ps = data.fixed.clone();
+	for i in 0..qs.len() {
+		ps[data.id[i]] = qs[i];
+	}
+	Some(ps)
+}
+
+/// Make the assignment ps of every unit, of objective value, the incumbent if it is better, and return whether it is.
+fn offer(groups: &Groups, data: &mut Data, ps: &Vec<usize>, value: i128, source: &str) -> bool {
+	let mut incumbent = data.shared.incumbent.lock().unwrap();
+	let better = value > incumbent.0;
+	if better {
+		event!(1, "update", worker = data.worker, source = source, path = data.dir(), depth = data.path.len(), incumbent = value,
+			nodes = data.shared.nodes.load(Ordering::Relaxed));
+		let ps = groups.expand(ps);
+		write_solution(&ps, &data.config.out);
+		*incumbent = (value, Some(ps));
+	}
+	data.lb = data.lb.max(incumbent.0);
+	better
+}
+
+/// The room left in gift j by the relaxation: its capacity less the size of the units fixed to it.
+fn room(data: &Data, g: &mincostcirculation::Graph<i32, i128>, j: usize) -> i32 {
+	let n = data.mul.len();
+	let r = n + data.cap.len();
+	let e = g.es[n + j].iter().find(|e| e.to == r).unwrap();
+	e.cap + g.es[r][e.rev].cap
+}
+
+/// Fractional diving from the current node: fix the fractional group of largest fraction to its gift, re-solving the relaxation
+/// incrementally, until no group is fractional, and offer the packed solution. The fixes are undone afterwards.
+fn dive(groups: &Groups, data: &mut Data, g: &mut mincostcirculation::Graph<i32, i128>) {
+	let mut fixes = vec![];
+	let sub = loop {
+		if data.score <= data.lb {
+			break None;
+		}
+		let mut cs = candidates(data, g);
+		if cs.is_empty() {
+			break Some(relaxed_assignment(data, g));
+		}
+		cs.sort_by(|a, b| b.frac.partial_cmp(&a.frac).unwrap());
+		match cs.iter().find(|c| room(data, g, c.gift) >= data.mul[c.unit]) {
+			Some(c) => {
+				let d = Decision { unit: c.unit, gift: c.gift, fix: true, bound: 0, other: None, second: false };
+				set(data, g, &d, true);
+				fixes.push(d);
+			}
+			None => break None,
+		}
+	};
+	let bound = data.score;
+	let ps = sub.and_then(|sub| complete(data, sub));
+	for d in fixes.iter().rev() {
+		set(data, g, d, false);
+	}
+	event!(2, "dive", worker = data.worker, depth = fixes.len(), bound = bound, found = ps.is_some());
+	if let Some(ps) = ps {
+		let value = data.value(&ps);
+		offer(groups, data, &ps, value, "dive");
+	}
+}
+
+/// Rounding of the current relaxation: the integral assignments are kept, the fractional groups in decreasing order of fraction
+/// get their gift while it has room, and packing places the rest, or if it fails, every fractional group.
+fn rounding(groups: &Groups, data: &mut Data, g: &mincostcirculation::Graph<i32, i128>) {
+	let mut sub = relaxed_assignment(data, g);
+	let mut cs = candidates(data, g);
+	cs.sort_by(|a, b| b.frac.partial_cmp(&a.frac).unwrap());
+	for c in &cs {
+		sub[c.unit] = !0;
+	}
+	let mut count = vec![0; data.cap.len()];
+	for i in 0..sub.len() {
+		if sub[i] != !0 {
+			count[sub[i]] += data.mul[i];
+		}
+	}
+	for c in &cs {
+		if sub[c.unit] == !0 && count[c.gift] + data.mul[c.unit] <= data.cap[c.gift] {
+			sub[c.unit] = c.gift;
+			count[c.gift] += data.mul[c.unit];
+		}
+	}
+	let ps = complete(data, sub.clone()).or_else(|| {
+		for c in &cs {
+			sub[c.unit] = !0;
+		}
+		complete(data, sub)
+	});
+	event!(2, "rounding", worker = data.worker, fractional = cs.len(), found = ps.is_some());
+	if let Some(ps) = ps {
+		let value = data.value(&ps);
+		offer(groups, data, &ps, value, "rounding");
+	}
+}
+
+/// Take the decision d in the relaxation and push it on the path; its bound is the new relaxation.
+fn take(data: &mut Data, g: &mut mincostcirculation::Graph<i32, i128>, d: Decision) {
+	set(data, g, &d, true);
+	assert_eq!(data.score, d.bound);
+	data.path.push(d);
+}
+
+/// Move the relaxation from the node at the end of data.path to the node at the end of path,
+/// undoing the decisions below their deepest common ancestor and taking those of path.
+fn goto(data: &mut Data, g: &mut mincostcirculation::Graph<i32, i128>, path: Vec<Decision>) {
+	let k = data.path.iter().zip(&path).take_while(|&(a, b)| a == b).count();
+	while data.path.len() > k {
+		let d = data.path.pop().unwrap();
+		set(data, g, &d, false);
+	}
+	for &d in &path[k..] {
+		take(data, g, d);
+	}
+}
+
+/// The node to visit after a leaf or a pruned node: the opposite of the deepest open decision on the path,
+/// or else the open node of the queue with the largest bound. The flag tells whether it comes from the queue.
+fn next(data: &mut Data) -> Option<(Vec<Decision>, bool)> {
+	let lb = data.lb;
+	if let Some(k) = data.path.iter().rposition(|d| d.other.map_or(false, |b| b > lb)) {
+		let d = data.path[k];
+		let mut path = data.path[..k].to_vec();
+		path.push(d.opposite(d.other.unwrap()));
+		return Some((path, false));
+	}
+	while let Some((bound, _, path)) = data.queue.pop() {
+		if bound > lb {
+			return Some((path, true));
+		}
+	}
+	None
+}
+
+/// Search the subtrees of the node at the end of data.path, of the open decisions on the path and of the open nodes of the queue.
+/// Depth-first keeps the second child of a node as the open decision on the path, best-first puts both children in the queue,
+/// and hybrid dives into the first child and puts the second in the queue. The search goes on with the node given by next.
+fn search(groups: &Groups, data: &mut Data, g: &mut mincostcirculation::Graph<i32, i128>, rule: &mut dyn BranchingRule) {
+	loop {
+		if let Some(d) = visit(groups, data, g, rule) {
+			let opposite = d.other.map(|b| d.opposite(b));
+			// The first child has the larger bound, above the incumbent.
+			match data.config.selection {
+				Selection::Dfs => {
+					take(data, g, d);
+					continue;
 				}
-				for _ in 0..data.mul[i] {
-					data.score -= cost;
-					data.score -= g.inc(i, n + j);
-					data.score -= g.inc(r, i);
-					data.score -= g.inc(n + j, r);
+				Selection::Hybrid => {
+					if let Some(e) = opposite {
+						data.enqueue(e);
+					}
+					take(data, g, Decision { other: None, ..d });
+					continue;
 				}
-			} else {
-				for _ in 0..data.mul[i] {
-					data.score -= g.inc(i, n + j);
+				Selection::Best => {
+					data.enqueue(Decision { other: None, ..d });
+					if let Some(e) = opposite {
+						data.enqueue(e);
+					}
 				}
 			}
 		}
-	} else {
-		let mut ps = data.fixed.clone();
-		for i in 0..n {
-			for e in &g.es[i] {
-				if n <= e.to && e.to < r && g.es[e.to][e.rev].cap > 0 {
-					ps[data.id[i]] = e.to - n;
+		if data.halted {
+			return;
+		}
+		match next(data) {
+			Some((path, queued)) => {
+				goto(data, g, path);
+				let d = data.path[data.path.len() - 1];
+				if queued {
+					event!(2, "jump", worker = data.worker, path = data.dir(), depth = data.path.len(), bound = d.bound, open = data.queue.len());
+				} else {
+					event!(2, "branch", worker = data.worker, path = data.dir(), depth = data.path.len() - 1, unit = data.id[d.unit], gift = d.gift,
+						fix = d.fix, bound = d.bound);
 				}
 			}
+			None => return,
 		}
-		let mut sub = vec![!0; n];
-		for i in 0..n {
-			sub[i] = ps[data.id[i]];
-		}
-		if let Some(qs) = packing(sub, &data.mul, &data.cap) {
-			eprintln!("{}: update: {}", dir, data.score);
-			data.lb = data.score;
-			for i in 0..n {
-				ps[data.id[i]] = qs[i];
+	}
+}
+
+/// The work of one thread: take open nodes from the pool and search their subtrees, until every worker is idle
+/// with no node left or the search stops, in which case the open nodes of the worker go back to the pool.
+fn work(groups: &Groups, mut data: Data, mut g: mincostcirculation::Graph<i32, i128>) {
+	let mut rule = data.config.branching.rule(data.mul.len());
+	while let Some((bound, path)) = data.wait() {
+		data.lb = data.lb.max(data.shared.incumbent.lock().unwrap().0);
+		if bound <= data.lb { continue }
+		goto(&mut data, &mut g, path);
+		search(groups, &mut data, &mut g, &mut *rule);
+		if data.halted {
+			let nodes = data.open_nodes();
+			let mut pool = data.shared.pool.lock().unwrap();
+			for (bound, path) in nodes {
+				pool.push(bound, path);
 			}
-			write_solution(&modify(&ps), "out.csv");
-		} else {
-			eprintln!("{}: packing failed: {}", dir, data.score);
+			return;
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+	Relax,
+	Reduce,
+	Full,
+}
+
+/// The branching rule to use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Branching {
+	Strong,
+	/// Strong branching on the given number of candidates.
+	Top(usize),
+	PseudoCost,
+	/// Reliability branching with the given reliability threshold.
+	Reliability(usize),
+}
+
+impl Branching {
+	/// A new rule for an instance of n units.
+	fn rule(&self, n: usize) -> Box<dyn BranchingRule> {
+		match *self {
+			Branching::Strong => Box::new(FullStrong),
+			Branching::Top(k) => Box::new(TopStrong { k }),
+			Branching::PseudoCost => Box::new(PseudoCost::new(n)),
+			Branching::Reliability(r) => Box::new(Reliability { costs: PseudoCost::new(n), reliability: r }),
 		}
 	}
 }
 
-struct Data {
+/// The order in which the open nodes are visited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Selection {
+	Dfs,
+	Best,
+	Hybrid,
+}
+
+#[derive(Clone)]
+struct Config {
+	out: String,
+	phase: Phase,
+	selection: Selection,
+	branching: Branching,
+	/// The heuristics run every that many nodes, from the first, if not 0.
+	heuristics: usize,
+	threads: usize,
+	deadline: Option<std::time::Instant>,
+	node_limit: usize,
+	stop_file: Option<String>,
+	/// The objective of a unit of the child sum.
+	child_unit: i128,
+	checkpoint: Option<String>,
+	checkpoint_interval: f64,
+	/// Checksum of the input files, to refuse resuming on another instance.
+	checksum: u64,
+}
+
+/// The open nodes not held by a worker, as (bound, sequence number, decisions from the root),
+/// and the state used to coordinate the workers.
+struct Pool {
+	nodes: BinaryHeap<(i128, usize, Vec<Decision>)>,
+	seq: usize,
+	/// Number of workers waiting for a node.
+	idle: usize,
+	/// Why the search stopped, if it did.
+	stop: Option<&'static str>,
+	last_checkpoint: std::time::Instant,
+	/// The checkpoint in progress: the open nodes added by the busy workers so far, and their number.
+	snapshot: Option<(Vec<(i128, Vec<Decision>)>, usize)>,
+	/// Number of checkpoints written, for the workers waiting for the one in progress.
+	checkpoints: usize,
+}
+
+impl Pool {
+	fn push(&mut self, bound: i128, path: Vec<Decision>) {
+		self.nodes.push((bound, self.seq, path));
+		self.seq += 1;
+	}
+}
+
+/// The state of the search shared by the workers.
+struct Shared {
+	threads: usize,
+	/// Number of nodes visited by all the workers.
+	nodes: AtomicUsize,
+	/// The value of the incumbent and its assignment of every child, which config.out also holds, if there is a solution of that value.
+	incumbent: Mutex<(i128, Option<Vec<usize>>)>,
+	pool: Mutex<Pool>,
+	/// Notified when the pool gets a node, a checkpoint is written, the search stops or every worker is idle.
+	cv: Condvar,
+	/// Copy of pool.idle, for the busy workers to check without the lock whether to share a node.
+	idle: AtomicUsize,
+}
+
+#[derive(Clone)]
+struct Data<'a> {
+	config: Config,
+	shared: &'a Shared,
+	/// The index of the worker, 0 when there is one.
+	worker: usize,
+	/// Whether visit returned because the search stopped, leaving the current node open.
+	halted: bool,
+	/// The decisions from the root to the current node.
+	path: Vec<Decision>,
+	/// The open nodes of best-first and hybrid selection as (bound, sequence number, decisions from the root);
+	/// among equal bounds the latest node is taken first.
+	queue: BinaryHeap<(i128, usize, Vec<Decision>)>,
+	seq: usize,
+	/// The score of the assignments fixed by reduce.
+	base_score: i128,
 	fixed: Vec<usize>,
 	id: Vec<usize>,
 	score: i128,
+	/// The incumbent as last seen by this worker.
 	lb: i128,
 	a: Vec<Vec<(usize, i128)>>,
 	mul: Vec<i32>,
 	cap: Vec<i32>,
 }
 
-fn reduce(data: &mut Data) {
+impl<'a> Data<'a> {
+	/// The objective value of the assignment ps of every unit that keeps the fixed units on their gifts:
+	/// base_score for the fixed units and the weights of the reduced instance for the others.
+	/// An edge removed by reduce counts as 0, so the value is at most the true one.
+	fn value(&self, ps: &Vec<usize>) -> i128 {
+		self.base_score + (0..self.a.len()).map(|i| get_cost(&self.a[i], ps[self.id[i]]).unwrap_or(0) * self.mul[i] as i128).sum::<i128>()
+	}
+	/// The decisions on the path, as + for a first decision whose opposite is open and - for an opposite decision.
+	fn dir(&self) -> String {
+		self.path.iter().map(|d| d.tag()).collect()
+	}
+	/// Put the child of the current node given by d in the queue, unless its bound is not above the incumbent.
+	fn enqueue(&mut self, d: Decision) {
+		if d.bound > self.lb {
+			let mut path = self.path.clone();
+			path.push(d);
+			self.queue.push((d.bound, self.seq, path));
+			self.seq += 1;
+		}
+	}
+	/// The open nodes held by this worker, as (bound, decisions from the root): the current node, which has not been visited,
+	/// the opposites of the open decisions on the path, and the queue.
+	fn open_nodes(&self) -> Vec<(i128, Vec<Decision>)> {
+		let lb = self.lb;
+		let path: Vec<Decision> = self.path.iter().map(|&d| Decision { other: None, ..d }).collect();
+		let mut nodes = vec![];
+		if self.score > lb {
+			nodes.push((self.score, path.clone()));
+		}
+		for k in 0..path.len() {
+			if let Some(b) = self.path[k].other.filter(|&b| b > lb) {
+				let mut p = path[..k].to_vec();
+				p.push(self.path[k].opposite(b));
+				nodes.push((b, p));
+			}
+		}
+		nodes.extend(self.queue.iter().filter(|o| o.0 > lb).map(|&(b, _, ref p)| (b, p.clone())));
+		nodes
+	}
+	/// Whether the search has stopped, or should because the node limit or the time limit has been reached or the stop file exists.
+	fn stopped(&self) -> bool {
+		let mut pool = self.shared.pool.lock().unwrap();
+		if pool.stop.is_none() {
+			let c = &self.config;
+			pool.stop = if self.shared.nodes.load(Ordering::Relaxed) >= c.node_limit {
+				Some("node limit")
+			} else if c.deadline.map_or(false, |d| std::time::Instant::now() >= d) {
+				Some("time limit")
+			} else if c.stop_file.as_ref().map_or(false, |f| std::path::Path::new(f).exists()) {
+				Some("stop file")
+			} else {
+				None
+			};
+			if pool.stop.is_some() {
+				// The final checkpoint is written once the workers are done.
+				pool.snapshot = None;
+				self.shared.cv.notify_all();
+			}
+		}
+		pool.stop.is_some()
+	}
+	/// Give an open node to the pool if it has fewer nodes than there are idle workers:
+	/// the opposite of the shallowest open decision on the path, which has the largest subtree, or else the best node of the queue.
+	fn share(&mut self) {
+		if self.shared.idle.load(Ordering::Relaxed) == 0 {
+			return;
+		}
+		let mut pool = self.shared.pool.lock().unwrap();
+		if pool.nodes.len() >= pool.idle {
+			return;
+		}
+		let lb = self.lb;
+		let (bound, path) = if let Some(k) = self.path.iter().position(|d| d.other.map_or(false, |b| b > lb)) {
+			let d = self.path[k];
+			self.path[k].other = None;
+			let mut path = self.path[..k].to_vec();
+			path.push(d.opposite(d.other.unwrap()));
+			(d.other.unwrap(), path)
+		} else {
+			loop {
+				match self.queue.pop() {
+					Some((b, _, path)) => if b > lb { break (b, path) },
+					None => return,
+				}
+			}
+		};
+		event!(2, "share", worker = self.worker, depth = path.len(), bound = bound, idle = pool.idle);
+		pool.push(bound, path);
+		self.shared.cv.notify_one();
+	}
+	/// Wait for a node of the pool, and return None once every worker is idle with no node left or the search has stopped.
+	fn wait(&self) -> Option<(i128, Vec<Decision>)> {
+		let shared = self.shared;
+		let mut pool = shared.pool.lock().unwrap();
+		pool.idle += 1;
+		shared.idle.store(pool.idle, Ordering::Relaxed);
+		self.try_checkpoint(&mut pool);
+		loop {
+			if pool.stop.is_some() {
+				return None;
+			}
+			// No node moves while a checkpoint is in progress.
+			if pool.snapshot.is_none() {
+				if let Some((bound, _, path)) = pool.nodes.pop() {
+					pool.idle -= 1;
+					shared.idle.store(pool.idle, Ordering::Relaxed);
+					return Some((bound, path));
+				}
+				if pool.idle == shared.threads {
+					shared.cv.notify_all();
+					return None;
+				}
+			}
+			pool = shared.cv.wait(pool).unwrap();
+		}
+	}
+	/// Once config.checkpoint_interval has passed since the last checkpoint, start one if none is in progress,
+	/// add the open nodes of this worker to it and wait until it is written.
+	fn sync(&self) {
+		if self.config.checkpoint.is_none() {
+			return;
+		}
+		let shared = self.shared;
+		let mut pool = shared.pool.lock().unwrap();
+		if pool.stop.is_some() {
+			return;
+		}
+		if pool.snapshot.is_none() {
+			if pool.last_checkpoint.elapsed().as_secs_f64() < self.config.checkpoint_interval {
+				return;
+			}
+			pool.snapshot = Some((vec![], 0));
+		}
+		{
+			let snapshot = pool.snapshot.as_mut().unwrap();
+			snapshot.0.extend(self.open_nodes());
+			snapshot.1 += 1;
+		}
+		let k = pool.checkpoints;
+		self.try_checkpoint(&mut pool);
+		while pool.checkpoints == k && pool.stop.is_none() {
+			pool = shared.cv.wait(pool).unwrap();
+		}
+	}
+	/// Write the checkpoint in progress once every worker has added its open nodes to it or is idle.
+	fn try_checkpoint(&self, pool: &mut Pool) {
+		match pool.snapshot {
+			Some((_, k)) if k + pool.idle == self.shared.threads => (),
+			_ => return,
+		}
+		let (mut nodes, _) = pool.snapshot.take().unwrap();
+		nodes.extend(pool.nodes.iter().map(|&(b, _, ref p)| (b, p.clone())));
+		self.checkpoint(&nodes);
+		pool.last_checkpoint = std::time::Instant::now();
+		pool.checkpoints += 1;
+		self.shared.cv.notify_all();
+	}
+	/// Write the reduced instance, the incumbent with its solution and the open nodes to config.checkpoint, if set,
+	/// followed by a checksum of the contents. The paths of the open nodes are replayed on the relaxation of the reduced instance by resume.
+	fn checkpoint(&self, nodes: &Vec<(i128, Vec<Decision>)>) {
+		let file = match self.config.checkpoint {
+			Some(ref file) => file.clone(),
+			None => return,
+		};
+		let tmp = format!("{}.tmp", file);
+		if let Err(e) = self.write_checkpoint(&tmp, nodes).and_then(|_| std::fs::rename(&tmp, &file)) {
+			event!(0, "checkpoint_failed", file = file.as_str(), error = e.to_string());
+			return;
+		}
+		event!(1, "checkpoint", file = file.as_str(), open = nodes.len(), nodes = self.shared.nodes.load(Ordering::Relaxed),
+			incumbent = self.shared.incumbent.lock().unwrap().0);
+	}
+	fn write_checkpoint(&self, file: &str, nodes: &Vec<(i128, Vec<Decision>)>) -> std::io::Result<()> {
+		use std::io::Write;
+		use santa17::cache::write_u64;
+		let mut w = Hashed::new(std::io::BufWriter::new(std::fs::File::create(file)?));
+		w.write_all(CHECKPOINT_MAGIC)?;
+		w.write_all(&CHECKPOINT_VERSION.to_le_bytes())?;
+		write_u64(&mut w, self.config.checksum)?;
+		{
+			let incumbent = self.shared.incumbent.lock().unwrap();
+			w.write_all(&incumbent.0.to_le_bytes())?;
+			w.write_all(&[incumbent.1.is_some() as u8])?;
+			if let Some(ref ps) = incumbent.1 {
+				write_u64(&mut w, ps.len() as u64)?;
+				for &j in ps {
+					write_u64(&mut w, j as u64)?;
+				}
+			}
+		}
+		write_u64(&mut w, self.shared.nodes.load(Ordering::Relaxed) as u64)?;
+		w.write_all(&self.base_score.to_le_bytes())?;
+		let mut fixed = self.fixed.clone();
+		for d in &self.path {
+			if d.fix {
+				fixed[self.id[d.unit]] = !0;
+			}
+		}
+		write_u64(&mut w, fixed.len() as u64)?;
+		for &j in &fixed {
+			write_u64(&mut w, j as u64)?;
+		}
+		write_u64(&mut w, self.id.len() as u64)?;
+		write_u64(&mut w, self.cap.len() as u64)?;
+		for i in 0..self.id.len() {
+			write_u64(&mut w, self.id[i] as u64)?;
+			w.write_all(&self.mul[i].to_le_bytes())?;
+			write_u64(&mut w, self.a[i].len() as u64)?;
+			for &(j, c) in &self.a[i] {
+				write_u64(&mut w, j as u64)?;
+				w.write_all(&c.to_le_bytes())?;
+			}
+		}
+		for &c in &self.cap {
+			w.write_all(&c.to_le_bytes())?;
+		}
+		write_u64(&mut w, nodes.len() as u64)?;
+		for &(bound, ref path) in nodes {
+			w.write_all(&bound.to_le_bytes())?;
+			write_path(&mut w, path)?;
+		}
+		let hash = w.hash;
+		w.inner.write_all(&hash.to_le_bytes())?;
+		w.inner.flush()
+	}
+	/// Restore the state written by checkpoint for an instance of the given number of children, and return the saved open nodes.
+	/// Every size is checked against the length of the file before allocating, and every unit and gift against the instance.
+	fn read_checkpoint(&mut self, file: &str, children: usize) -> std::io::Result<Vec<(i128, Vec<Decision>)>> {
+		use santa17::cache::{read_bytes, read_u64};
+		let invalid = |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string());
+		let f = std::fs::File::open(file)?;
+		let size = f.metadata()?.len();
+		let mut r = Hashed::new(std::io::BufReader::new(f));
+		let r = &mut r;
+		let (units, gifts) = (self.fixed.len(), self.cap.len());
+		let head = read_bytes(r, 12)?;
+		if &head[..8] != CHECKPOINT_MAGIC || head[8..] != CHECKPOINT_VERSION.to_le_bytes() {
+			return Err(invalid("not a checkpoint of this version"));
+		}
+		if read_u64(r)? != self.config.checksum {
+			return Err(invalid("the input files have changed"));
+		}
+		let read_i128 = |r: &mut Hashed<std::io::BufReader<std::fs::File>>| -> std::io::Result<i128> {
+			let mut b = [0; 16];
+			b.copy_from_slice(&read_bytes(r, 16)?);
+			Ok(i128::from_le_bytes(b))
+		};
+		let read_i32 = |r: &mut Hashed<std::io::BufReader<std::fs::File>>| -> std::io::Result<i32> {
+			let b = read_bytes(r, 4)?;
+			Ok(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+		};
+		self.lb = read_i128(r)?;
+		let solution = if read_bytes(r, 1)?[0] != 0 {
+			if read_u64(r)? != children as u64 {
+				return Err(invalid("wrong number of children"));
+			}
+			let ps = (0..children).map(|_| read_u64(r).map(|j| j as usize)).collect::<std::io::Result<Vec<usize>>>()?;
+			if ps.iter().any(|&j| j >= gifts) {
+				return Err(invalid("gift out of range"));
+			}
+			Some(ps)
+		} else {
+			None
+		};
+		*self.shared.incumbent.lock().unwrap() = (self.lb, solution);
+		self.shared.nodes.store(read_u64(r)? as usize, Ordering::Relaxed);
+		self.base_score = read_i128(r)?;
+		if read_u64(r)? != units as u64 {
+			return Err(invalid("wrong number of units"));
+		}
+		for u in 0..units {
+			self.fixed[u] = read_u64(r)? as usize;
+			if self.fixed[u] != !0 && self.fixed[u] >= gifts {
+				return Err(invalid("gift out of range"));
+			}
+		}
+		let n = read_u64(r)?;
+		if n > units as u64 || read_u64(r)? != gifts as u64 {
+			return Err(invalid("wrong number of units or gifts"));
+		}
+		let n = n as usize;
+		self.id = vec![];
+		self.mul = vec![];
+		self.a = vec![];
+		let mut seen = vec![false; units];
+		for _ in 0..n {
+			let u = read_u64(r)? as usize;
+			if u >= units || seen[u] || self.fixed[u] != !0 {
+				return Err(invalid("unit out of range"));
+			}
+			seen[u] = true;
+			self.id.push(u);
+			self.mul.push(read_i32(r)?);
+			if self.mul[self.mul.len() - 1] < 1 {
+				return Err(invalid("empty unit"));
+			}
+			let len = read_u64(r)?;
+			if len > gifts as u64 {
+				return Err(invalid("more edges than gifts"));
+			}
+			let mut a = Vec::with_capacity(len as usize);
+			for _ in 0..len {
+				let j = read_u64(r)? as usize;
+				if j >= gifts {
+					return Err(invalid("gift out of range"));
+				}
+				a.push((j, read_i128(r)?));
+			}
+			self.a.push(a);
+		}
+		self.cap = (0..gifts).map(|_| read_i32(r)).collect::<std::io::Result<_>>()?;
+		let len = read_u64(r)?;
+		// Every node takes at least 24 bytes.
+		if len > size / 24 {
+			return Err(invalid("more open nodes than the file holds"));
+		}
+		let mut nodes = Vec::with_capacity(len as usize);
+		for _ in 0..len {
+			let bound = read_i128(r)?;
+			nodes.push((bound, read_path(r, n, gifts)?));
+		}
+		let hash = r.hash;
+		if read_u64(&mut r.inner)? != hash {
+			return Err(invalid("damaged checkpoint"));
+		}
+		Ok(nodes)
+	}
+	/// Report the incumbent and, if the search stopped, the best bound of the open nodes of the pool and the gap.
+	fn summary(&self) {
+		let pool = self.shared.pool.lock().unwrap();
+		let (lb, saved) = {
+			let incumbent = self.shared.incumbent.lock().unwrap();
+			(incumbent.0, incumbent.1.is_some())
+		};
+		let unit = self.config.child_unit as f64;
+		let incumbent = if lb >= 0 { Some(lb) } else { None };
+		let open_ub = pool.nodes.iter().map(|o| o.0).filter(|&b| b > lb).max();
+		let gap = open_ub.map(|ub| ub - lb.max(0));
+		event!(0, "summary", stop = pool.stop, nodes = self.shared.nodes.load(Ordering::Relaxed), incumbent = incumbent,
+			incumbent_child_sum = incumbent.map(|v| v as f64 / unit), saved = saved, out = self.config.out.as_str(), open_bound = open_ub,
+			open_child_sum = open_ub.map(|v| v as f64 / unit), gap = gap, gap_child_sum = gap.map(|v| v as f64 / unit), optimal = open_ub.is_none());
+	}
+}
+
+const CHECKPOINT_MAGIC: &[u8; 8] = b"SANTA17C";
+const CHECKPOINT_VERSION: u32 = 4;
+
+/// A reader or writer that hashes the bytes going through it with FNV-1a, for the checksum at the end of a checkpoint.
+struct Hashed<T> {
+	inner: T,
+	hash: u64,
+}
+
+impl<T> Hashed<T> {
+	fn new(inner: T) -> Hashed<T> {
+		Hashed { inner, hash: 0xcbf29ce484222325 }
+	}
+	fn add(&mut self, buf: &[u8]) {
+		for &b in buf {
+			self.hash = (self.hash ^ b as u64).wrapping_mul(0x100000001b3);
+		}
+	}
+}
+
+impl<W: std::io::Write> std::io::Write for Hashed<W> {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		let k = self.inner.write(buf)?;
+		self.add(&buf[..k]);
+		Ok(k)
+	}
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+impl<R: std::io::Read> std::io::Read for Hashed<R> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let k = self.inner.read(buf)?;
+		self.add(&buf[..k]);
+		Ok(k)
+	}
+}
+
+fn write_path<W: std::io::Write>(w: &mut W, path: &Vec<Decision>) -> std::io::Result<()> {
+	santa17::cache::write_u64(w, path.len() as u64)?;
+	for d in path {
+		santa17::cache::write_u64(w, d.unit as u64)?;
+		santa17::cache::write_u64(w, d.gift as u64)?;
+		w.write_all(&[d.fix as u8, d.other.is_some() as u8, d.second as u8])?;
+		w.write_all(&d.bound.to_le_bytes())?;
+		w.write_all(&d.other.unwrap_or(0).to_le_bytes())?;
+	}
+	Ok(())
+}
+
+/// A path written by write_path, for a reduced instance of n units and m gifts.
+fn read_path<R: std::io::Read>(r: &mut R, n: usize, m: usize) -> std::io::Result<Vec<Decision>> {
+	use santa17::cache::{read_bytes, read_u64};
+	let read_i128 = |r: &mut R| -> std::io::Result<i128> {
+		let mut b = [0; 16];
+		b.copy_from_slice(&read_bytes(r, 16)?);
+		Ok(i128::from_le_bytes(b))
+	};
+	let len = read_u64(r)? as usize;
+	let mut path = vec![];
+	for _ in 0..len {
+		let unit = read_u64(r)? as usize;
+		let gift = read_u64(r)? as usize;
+		let flags = read_bytes(r, 3)?;
+		let bound = read_i128(r)?;
+		let other = read_i128(r)?;
+		if unit >= n || gift >= m {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "decision out of range"));
+		}
+		path.push(Decision { unit, gift, fix: flags[0] != 0, bound, other: if flags[1] != 0 { Some(other) } else { None }, second: flags[2] != 0 });
+	}
+	Ok(path)
+}
+
+/// Fix the units whose assignment cannot change in a solution better than data.lb, and drop the edges that cannot be used.
+/// Return false, without fixing anything, if the relaxation is not above data.lb so that there is no better solution.
+/// The bound and the result are progress events, and the results of the reduce phase.
+fn reduce(data: &mut Data) -> bool {
 	let n = data.mul.len();
 	let m = data.cap.len();
 	let g = solve_relax(data);
 	let r = n + m;
 	let ub = data.score - g.val::<i128>();
-	eprintln!("ub = {}", ub);
+	let level = if data.config.phase == Phase::Reduce { 0 } else { 1 };
+	event!(level, "relaxed", bound = ub);
+	if ub <= data.lb {
+		event!(level, "reduced", units = n, fixed = 0, free = n, improvable = false);
+		return false;
+	}
 	let mut es = vec![vec![]; n + m + 1];
 	for i in 0..n + m + 1 {
 		for e in &g.es[i] {
@@ -286,7 +1249,7 @@ fn reduce(data: &mut Data) {
 	for j in 0..m {
 		let s = n + j;
 		let mut dist = vec![None; n + m + 1];
-		let mut que = ::std::collections::BinaryHeap::new();
+		let mut que = BinaryHeap::new();
 		dist[s] = Some(0);
 		que.push((s, 0));
 		while let Some((u, d)) = que.pop() {
@@ -333,17 +1296,18 @@ fn reduce(data: &mut Data) {
 			data.score += get_cost(&data.a[i], fixed[i]).unwrap() * data.mul[i] as i128;
 		}
 	}
-	eprintln!("reduced: {} -> {}", n, id2.len());
+	event!(level, "reduced", units = n, fixed = n - id2.len(), free = id2.len(), improvable = true);
 	data.id = id2;
 	data.a = a2;
 	data.mul = mul2;
 	data.cap = cap2;
+	true
 }
 
 fn solve_relax(data: &Data) -> mincostcirculation::Graph<i32, i128> {
 	let n = data.mul.len();
 	let m = data.cap.len();
-	eprintln!("solving the relaxed problem...");
+	event!(1, "relax", units = n);
 	let mut g = mincostcirculation::Graph::new(n + m + 1);
 	let r = n + m;
 	for i in 0..n {
@@ -361,37 +1325,197 @@ fn solve_relax(data: &Data) -> mincostcirculation::Graph<i32, i128> {
 	g
 }
 
-pub fn solve(a: Vec<Vec<(usize, i128)>>, mul: Vec<i32>, cap: Vec<i32>, lb: i128) {
+/// With `resume`, the search continues from the open nodes of the checkpoint instead of starting from the root.
+/// The workers start from copies of the relaxation of the reduced instance, and worker 0 runs on this thread.
+fn solve(groups: &Groups, a: Vec<Vec<(usize, i128)>>, mul: Vec<i32>, cap: Vec<i32>, lb: i128, init: Option<Vec<usize>>, resume: bool, config: Config) {
 	let n = mul.len();
-	let mut data = Data { a, mul, cap, lb, score: 0, fixed: vec![!0; n], id: (0..n).collect() };
-	reduce(&mut data);
-	let mut g = solve_relax(&data);
+	let shared = Shared {
+		threads: config.threads,
+		nodes: AtomicUsize::new(0),
+		incumbent: Mutex::new((lb, init)),
+		pool: Mutex::new(Pool {
+			nodes: BinaryHeap::new(), seq: 0, idle: 0, stop: None, last_checkpoint: std::time::Instant::now(), snapshot: None, checkpoints: 0,
+		}),
+		cv: Condvar::new(),
+		idle: AtomicUsize::new(0),
+	};
+	let mut data = Data {
+		config, shared: &shared, worker: 0, halted: false, path: vec![], queue: BinaryHeap::new(), seq: 0, base_score: 0,
+		a, mul, cap, lb, score: 0, fixed: vec![!0; n], id: (0..n).collect(),
+	};
+	if data.config.phase == Phase::Relax {
+		let g = solve_relax(&data);
+		event!(0, "relaxed", bound = -g.val::<i128>());
+		return;
+	}
+	let mut nodes = vec![];
+	// Whether the search starts from the root, which reduce may find cannot improve the incumbent.
+	let mut root = false;
+	if resume {
+		let file = data.config.checkpoint.clone().unwrap();
+		let children = groups.members.iter().map(|ms| ms.len()).sum::<usize>() + groups.singles.len();
+		nodes = data.read_checkpoint(&file, children).unwrap_or_else(|e| {
+			eprintln!("{}: {}", file, e);
+			std::process::exit(1);
+		});
+		// The output may be another file than that of the stopped search.
+		if let Some(ref ps) = shared.incumbent.lock().unwrap().1 {
+			write_solution(ps, &data.config.out);
+		}
+		data.score = data.base_score;
+		event!(1, "resume", file = file.as_str(), open = nodes.len(), nodes = shared.nodes.load(Ordering::Relaxed), incumbent = data.lb);
+	} else {
+		root = reduce(&mut data);
+		data.base_score = data.score;
+	}
+	if data.config.phase == Phase::Reduce {
+		return;
+	}
+	let g = solve_relax(&data);
 	data.score += -g.val::<i128>();
-	rec(&mut data, &mut g, "");
+	if root {
+		nodes.push((data.score, vec![]));
+	}
+	{
+		let mut pool = shared.pool.lock().unwrap();
+		for (bound, path) in nodes {
+			pool.push(bound, path);
+		}
+	}
+	std::thread::scope(|s| {
+		for w in 1..shared.threads {
+			let mut data = data.clone();
+			data.worker = w;
+			let g = g.clone();
+			s.spawn(move || work(groups, data, g));
+		}
+		work(groups, data.clone(), g);
+	});
+	let stopped = shared.pool.lock().unwrap().stop.is_some();
+	if stopped {
+		let pool = shared.pool.lock().unwrap();
+		data.checkpoint(&pool.nodes.iter().map(|&(b, _, ref p)| (b, p.clone())).collect());
+	} else if let Some(ref file) = data.config.checkpoint {
+		let _ = std::fs::remove_file(file);
+	}
+	data.summary();
+}
+
+/// The solution in `file` and its weights. It also becomes the first incumbent in `out`.
+fn warm_start(spec: &ProblemSpec, groups: &Groups, cap: &Vec<usize>, g1: &PreferenceGraph, g2: &PreferenceGraph, file: &str, out: &str) -> (Vec<usize>, i64, i64) {
+	let ps = read_solution(file).or_exit();
+	let rows = ps.iter().enumerate().map(|(i, &j)| (i + 2, i, j)).collect();
+	let (report, _) = validate::validate(spec, groups, cap, &rows);
+	if !report.is_ok() {
+		eprintln!("{}: {}", file, report);
+		std::process::exit(2);
+	}
+	let mut score1 = 0;
+	let mut score2 = 0;
+	for i in 0..spec.children {
+		score1 += g1.get(i, ps[i]).unwrap_or(0);
+		score2 += g2.get(i, ps[i]).unwrap_or(0);
+	}
+	event!(1, "warm_start", file = file, child_sum = score1, gift_sum = score2, score = get_score(spec, score1, score2));
+	write_solution(&ps, out);
+	(ps, score1, score2)
 }
 
 fn main() {
-	let (g1, g2) = construct_graph();
+	let args = Args::from_env(USAGE);
+	if args.positional.len() > 0 || args.has("help") {
+		eprintln!("{}", USAGE);
+		std::process::exit(1);
+	}
+	let sink: Box<dyn std::io::Write + Send> = match args.get::<String>("log") {
+		Some(file) => Box::new(std::fs::File::create(&file).unwrap_or_else(|e| {
+			eprintln!("{}: {}", file, e);
+			std::process::exit(1);
+		})),
+		None => Box::new(std::io::stderr()),
+	};
+	let json = match args.get_or("log-format", "text".to_string()).as_str() {
+		"text" => false,
+		"json" => true,
+		v => args.invalid("log-format", v),
+	};
+	events::init(sink, json, args.get_or("verbose", 1));
+	let mut config = Config {
+		out: args.get_or("out", "out.csv".to_string()),
+		phase: match args.get_or("phase", "full".to_string()).as_str() {
+			"relax" => Phase::Relax,
+			"reduce" => Phase::Reduce,
+			"full" => Phase::Full,
+			v => args.invalid("phase", v),
+		},
+		selection: match args.get_or("node-selection", "dfs".to_string()).as_str() {
+			"dfs" => Selection::Dfs,
+			"best" => Selection::Best,
+			"hybrid" => Selection::Hybrid,
+			v => args.invalid("node-selection", v),
+		},
+		deadline: args.get::<f64>("time").map(|t| std::time::Instant::now() + std::time::Duration::from_secs_f64(t)),
+		branching: match args.get_or("branching", "strong".to_string()).as_str() {
+			"strong" => Branching::Strong,
+			"top" => Branching::Top(args.get_or("candidates", 8)),
+			"pseudocost" => Branching::PseudoCost,
+			"reliability" => Branching::Reliability(args.get_or("reliability", 4)),
+			v => args.invalid("branching", v),
+		},
+		heuristics: args.get_or("heuristics", 100),
+		threads: match args.get_or("threads", 1) {
+			0 => std::thread::available_parallelism().map_or(1, |k| k.get()),
+			k => k,
+		},
+		node_limit: args.get_or("nodes", usize::max_value()),
+		stop_file: args.get("stop-file"),
+		child_unit: 1,
+		checkpoint: args.get("checkpoint"),
+		checkpoint_interval: args.get_or("checkpoint-interval", 600.0),
+		checksum: 0,
+	};
+	let resume = args.has("resume");
+	if resume && !config.checkpoint.as_ref().map_or(false, |f| std::path::Path::new(f).exists()) {
+		eprintln!("--resume needs an existing --checkpoint FILE");
+		std::process::exit(1);
+	}
+	let files = Files::from_args(&args);
+	let (spec, g1, g2) = construct_graph(&files).or_exit();
+	let groups = load_groups(&spec, &files).or_exit();
 	let mut es = vec![];
 	let mut mul = vec![];
 	let scale = u64::max_value() as i128;
-	for i in 0..N3 {
-		mul.push(3);
-		let a = merge(&merge(&g1[i * 3], &g1[i * 3 + 1]), &g1[i * 3 + 2]).into_iter().map(|(j, w)| (j, (w * 2) as i128 * scale)).collect();
-		let b = merge(&merge(&g2[i * 3], &g2[i * 3 + 1]), &g2[i * 3 + 2]).into_iter().map(|(j, w)| (j, (w * 2) as i128)).collect();
-		es.push(merge(&a, &b));
-	}
-	for i in 0..N2 {
-		mul.push(2);
-		let a = merge(&g1[N3 * 3 + i * 2], &g1[N3 * 3 + i * 2 + 1]).into_iter().map(|(j, w)| (j, (w * 3) as i128 * scale)).collect();
-		let b = merge(&g2[N3 * 3 + i * 2], &g2[N3 * 3 + i * 2 + 1]).into_iter().map(|(j, w)| (j, (w * 3) as i128)).collect();
-		es.push(merge(&a, &b));
-	}
-	for i in N3 * 3 + N2 * 2..N {
-		mul.push(1);
-		let a = g1[i].iter().map(|&(j, w)| (j, (w * 6) as i128 * scale)).collect();
-		let b = g2[i].iter().map(|&(j, w)| (j, (w * 6) as i128)).collect();
-		es.push(merge(&a, &b));
-	}
-	solve(es, mul, vec![1000; 1000], 1179959622 * scale + 7703387);
-}
\ No newline at end of file
+	// A unit of size d gets weight l / d so that every child has weight l in total.
+	let mut l = 1;
+	for u in 0..groups.units() {
+		let d = groups.unit(u).len() as i64;
+		l = l / gcd(l as i128, d as i128) as i64 * d;
+	}
+	for u in 0..groups.units() {
+		let ms = groups.unit(u);
+		mul.push(ms.len() as i32);
+		let k = l / ms.len() as i64;
+		let mut a = vec![];
+		let mut b = vec![];
+		for &i in ms {
+			a = merge(a, g1.row(i));
+			b = merge(b, g2.row(i));
+		}
+		let a = a.into_iter().map(|(j, w)| (j, (w * k) as i128 * scale));
+		let b = b.into_iter().map(|(j, w)| (j, (w * k) as i128));
+		es.push(merge(a, b));
+	}
+	let cap = load_capacities(&spec, &files).or_exit();
+	// Every child contributes l times its weights, and only solutions better than lb are searched for.
+	config.child_unit = l as i128 * scale;
+	config.checksum = cache::checksum(&[&files.child, &files.gift, &files.spec, &files.groups, &files.capacity]).or_exit();
+	let (lb, init) = match args.get::<String>("init").filter(|_| !resume) {
+		Some(file) => {
+			let (ps, score1, score2) = warm_start(&spec, &groups, &cap, &g1, &g2, &file, &config.out);
+			(l as i128 * (score1 as i128 * scale + score2 as i128), Some(ps))
+		}
+		None => (args.get_or("lb", -1), None),
+	};
+	let cap = cap.into_iter().map(|c| c as i32).collect();
+	solve(&groups, es, mul, cap, lb, init, resume, config);
+}
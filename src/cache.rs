@@ -0,0 +1,162 @@
+//! Binary cache of the graphs built by construct_graph.
+//!
+//! Layout (little endian): MAGIC, VERSION: u32, checksum of the sources: u64, the spec, and then g and g2,
+//! each as the number of rows: u64, the number of entries: u64, offset: [u64], to: [u32] and weight: [i32].
+
+use super::*;
+use std::io::{Read, Write};
+
+pub const MAGIC: &[u8; 8] = b"SANTA17G";
+pub const VERSION: u32 = 2;
+
+/// FNV-1a over the contents of the files. A missing file is hashed differently from an empty one.
+pub fn checksum(files: &[&str]) -> Result<u64, InputError> {
+	let mut h = 0xcbf29ce484222325u64;
+	let mut buf = vec![0; 1 << 16];
+	for &file in files {
+		if !std::path::Path::new(file).exists() {
+			h = (h ^ 0xff).wrapping_mul(0x100000001b3);
+			continue;
+		}
+		let mut f = std::fs::File::open(file).map_err(|e| io_error(file, e))?;
+		loop {
+			let k = f.read(&mut buf).map_err(|e| io_error(file, e))?;
+			if k == 0 { break }
+			for &b in &buf[..k] {
+				h = (h ^ b as u64).wrapping_mul(0x100000001b3);
+			}
+		}
+		h = (h ^ 0xfe).wrapping_mul(0x100000001b3);
+	}
+	Ok(h)
+}
+
+pub fn write_u64<W: Write>(w: &mut W, x: u64) -> std::io::Result<()> {
+	w.write_all(&x.to_le_bytes())
+}
+
+fn write_str<W: Write>(w: &mut W, s: &str) -> std::io::Result<()> {
+	write_u64(w, s.len() as u64)?;
+	w.write_all(s.as_bytes())
+}
+
+fn write_graph<W: Write>(w: &mut W, g: &PreferenceGraph) -> std::io::Result<()> {
+	write_u64(w, g.rows() as u64)?;
+	write_u64(w, g.to.len() as u64)?;
+	for &o in &g.offset {
+		write_u64(w, o as u64)?;
+	}
+	for &j in &g.to {
+		w.write_all(&j.to_le_bytes())?;
+	}
+	for &c in &g.weight {
+		w.write_all(&c.to_le_bytes())?;
+	}
+	Ok(())
+}
+
+/// Write to file.tmp and rename it, so that an interrupted write leaves no partial cache.
+pub fn write(file: &str, checksum: u64, spec: &ProblemSpec, g: &PreferenceGraph, g2: &PreferenceGraph) -> std::io::Result<()> {
+	let tmp = format!("{}.tmp", file);
+	write_file(&tmp, checksum, spec, g, g2)?;
+	std::fs::rename(&tmp, file)
+}
+
+fn write_file(file: &str, checksum: u64, spec: &ProblemSpec, g: &PreferenceGraph, g2: &PreferenceGraph) -> std::io::Result<()> {
+	let mut w = std::io::BufWriter::new(std::fs::File::create(file)?);
+	w.write_all(MAGIC)?;
+	w.write_all(&VERSION.to_le_bytes())?;
+	write_u64(&mut w, checksum)?;
+	for &x in &[spec.children, spec.gifts, spec.gift_capacity, spec.triplets, spec.twins, spec.wishlist_len, spec.goodkids_len] {
+		write_u64(&mut w, x as u64)?;
+	}
+	write_str(&mut w, &spec.child_happiness)?;
+	write_str(&mut w, &spec.gift_happiness)?;
+	write_graph(&mut w, g)?;
+	write_graph(&mut w, g2)?;
+	w.flush()
+}
+
+pub fn read_bytes<R: Read>(r: &mut R, n: usize) -> std::io::Result<Vec<u8>> {
+	let mut buf = vec![0; n];
+	r.read_exact(&mut buf)?;
+	Ok(buf)
+}
+
+pub fn read_u64<R: Read>(r: &mut R) -> std::io::Result<u64> {
+	let mut buf = [0; 8];
+	r.read_exact(&mut buf)?;
+	Ok(u64::from_le_bytes(buf))
+}
+
+fn invalid(msg: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// The sizes are checked against the length of the file before allocating anything.
+fn read_str<R: Read>(r: &mut R, len: u64) -> std::io::Result<String> {
+	let n = read_u64(r)?;
+	if n > len {
+		return Err(invalid("string longer than the file"));
+	}
+	String::from_utf8(read_bytes(r, n as usize)?).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+fn read_graph<R: Read>(r: &mut R, len: u64, gifts: usize) -> std::io::Result<PreferenceGraph> {
+	let (n, k) = (read_u64(r)?, read_u64(r)?);
+	if n >= len / 8 || k > len / 8 {
+		return Err(invalid("graph larger than the file"));
+	}
+	let (n, k) = (n as usize, k as usize);
+	let offset: Vec<usize> = read_bytes(r, (n + 1) * 8)?.chunks(8).map(|b| {
+		let mut x = [0; 8];
+		x.copy_from_slice(b);
+		u64::from_le_bytes(x) as usize
+	}).collect();
+	if offset[0] != 0 || offset.windows(2).any(|w| w[0] > w[1]) || offset[n] != k {
+		return Err(invalid("offsets not increasing from 0 to the number of entries"));
+	}
+	let to: Vec<u32> = read_bytes(r, k * 4)?.chunks(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+	if to.iter().any(|&j| j as usize >= gifts) {
+		return Err(invalid("gift out of range"));
+	}
+	let weight = read_bytes(r, k * 4)?.chunks(4).map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+	Ok(PreferenceGraph { offset, to, weight })
+}
+
+/// Read the cache if it exists, was built from sources with the given checksum and is well formed.
+/// A damaged cache is ignored, and rebuilt by construct_graph.
+pub fn read(file: &str, checksum: u64) -> Option<(ProblemSpec, PreferenceGraph, PreferenceGraph)> {
+	let f = std::fs::File::open(file).ok()?;
+	let len = f.metadata().ok()?.len();
+	let mut r = std::io::BufReader::new(f);
+	let head = read_bytes(&mut r, 20).ok()?;
+	if &head[..8] != MAGIC || head[8..12] != VERSION.to_le_bytes() || head[12..] != checksum.to_le_bytes() {
+		return None;
+	}
+	read_body(&mut r, len).ok()
+}
+
+fn read_body<R: Read>(r: &mut R, len: u64) -> std::io::Result<(ProblemSpec, PreferenceGraph, PreferenceGraph)> {
+	let mut xs = [0; 7];
+	for x in &mut xs {
+		*x = read_u64(r)? as usize;
+	}
+	let spec = ProblemSpec {
+		children: xs[0],
+		gifts: xs[1],
+		gift_capacity: xs[2],
+		triplets: xs[3],
+		twins: xs[4],
+		wishlist_len: xs[5],
+		goodkids_len: xs[6],
+		child_happiness: read_str(r, len)?,
+		gift_happiness: read_str(r, len)?,
+	};
+	let g = read_graph(r, len, spec.gifts)?;
+	let g2 = read_graph(r, len, spec.gifts)?;
+	if g.rows() != spec.children || g2.rows() != spec.children {
+		return Err(invalid("wrong number of rows"));
+	}
+	Ok((spec, g, g2))
+}
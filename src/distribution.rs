@@ -0,0 +1,146 @@
+//! How the children and the gifts did in a solution, broken down by the size of the unit of the child.
+//!
+//! The ranks are recovered from the weights of the graphs, so entries of equal happiness are counted at the best rank sharing it.
+//! The last bucket of each histogram counts the pairs not on the list.
+
+use super::*;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Statistics of the children in units of one size.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+	pub size: usize,
+	pub children: usize,
+	/// Number of children per rank of their gift in their wishlist.
+	pub child_rank: Vec<usize>,
+	/// Number of children per rank in the list of their gift.
+	pub gift_rank: Vec<usize>,
+	/// Sums of the actual happiness (not shifted by the happiness of the entries not on the list).
+	pub child_happiness: i64,
+	pub gift_happiness: i64,
+}
+
+impl Stats {
+	fn new(size: usize, spec: &ProblemSpec) -> Stats {
+		Stats { size, child_rank: vec![0; spec.wishlist_len + 1], gift_rank: vec![0; spec.goodkids_len + 1], ..Stats::default() }
+	}
+	fn add(&mut self, s: &Stats) {
+		self.children += s.children;
+		for (a, b) in self.child_rank.iter_mut().zip(&s.child_rank) {
+			*a += b;
+		}
+		for (a, b) in self.gift_rank.iter_mut().zip(&s.gift_rank) {
+			*a += b;
+		}
+		self.child_happiness += s.child_happiness;
+		self.gift_happiness += s.gift_happiness;
+	}
+	fn name(&self) -> String {
+		match self.size {
+			0 => "all".to_string(),
+			1 => "singles".to_string(),
+			2 => "twins".to_string(),
+			3 => "triplets".to_string(),
+			k => format!("groups of {}", k),
+		}
+	}
+	fn mean(&self, x: i64) -> f64 {
+		if self.children == 0 { 0.0 } else { x as f64 / self.children as f64 }
+	}
+	fn on_list(&self, rank: &Vec<usize>) -> f64 {
+		if self.children == 0 { 0.0 } else { 100.0 * (self.children - rank[rank.len() - 1]) as f64 / self.children as f64 }
+	}
+}
+
+/// Stats per unit size in increasing order, followed by their total with size 0.
+#[derive(Clone, Debug)]
+pub struct Distribution {
+	pub stats: Vec<Stats>,
+}
+
+/// Weight -> the smallest rank of that weight.
+fn ranks(m: &dyn HappinessModel, len: usize) -> HashMap<i64, usize> {
+	(0..len).rev().map(|r| (m.weight(r, len), r)).collect()
+}
+
+/// Gifts >= spec.gifts (e.g. !0 for none) count as not on either list.
+pub fn distribution(spec: &ProblemSpec, groups: &Groups, g1: &PreferenceGraph, g2: &PreferenceGraph, ps: &Vec<usize>) -> Distribution {
+	let (cm, gm) = (spec.child_model(), spec.gift_model());
+	let (r1, r2) = (ranks(&*cm, spec.wishlist_len), ranks(&*gm, spec.goodkids_len));
+	let (m1, m2) = (cm.missing(spec.wishlist_len), gm.missing(spec.goodkids_len));
+	let mut by_size: Vec<Stats> = vec![];
+	for u in 0..groups.units() {
+		let ms = groups.unit(u);
+		while by_size.len() < ms.len() {
+			let k = by_size.len() + 1;
+			by_size.push(Stats::new(k, spec));
+		}
+		let s = &mut by_size[ms.len() - 1];
+		for &i in ms {
+			s.children += 1;
+			let (w1, w2) = (g1.get(i, ps[i]), g2.get(i, ps[i]));
+			s.child_rank[w1.map_or(spec.wishlist_len, |w| r1[&w])] += 1;
+			s.gift_rank[w2.map_or(spec.goodkids_len, |w| r2[&w])] += 1;
+			s.child_happiness += w1.unwrap_or(0) + m1;
+			s.gift_happiness += w2.unwrap_or(0) + m2;
+		}
+	}
+	let mut all = Stats::new(0, spec);
+	for s in &by_size {
+		all.add(s);
+	}
+	let mut stats: Vec<Stats> = by_size.into_iter().filter(|s| s.children > 0).collect();
+	stats.push(all);
+	Distribution { stats }
+}
+
+fn rank(r: usize, len: usize) -> String {
+	if r == len - 1 { "none".to_string() } else { r.to_string() }
+}
+
+impl Distribution {
+	/// Rows of kind,size,rank,count with kind child or gift, size 0 for all the children and rank none for not on the list.
+	pub fn to_csv(&self) -> String {
+		let mut out = "kind,size,rank,count\n".to_string();
+		for (kind, f) in &[("child", (|s: &Stats| &s.child_rank) as fn(&Stats) -> &Vec<usize>), ("gift", |s: &Stats| &s.gift_rank)] {
+			for s in &self.stats {
+				let hist = f(s);
+				for r in 0..hist.len() {
+					out += &format!("{},{},{},{}\n", kind, s.size, rank(r, hist.len()), hist[r]);
+				}
+			}
+		}
+		out
+	}
+	/// An array of the stats, each with the histograms as arrays whose last element is for not on the list.
+	pub fn to_json(&self) -> String {
+		let list = |v: &Vec<usize>| v.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(",");
+		let items: Vec<String> = self.stats.iter().map(|s| format!(
+			"{{\"size\":{},\"children\":{},\"child_happiness\":{},\"gift_happiness\":{},\"child_rank\":[{}],\"gift_rank\":[{}]}}",
+			s.size, s.children, s.child_happiness, s.gift_happiness, list(&s.child_rank), list(&s.gift_rank))).collect();
+		format!("[{}]", items.join(","))
+	}
+}
+
+impl fmt::Display for Distribution {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for s in &self.stats {
+			writeln!(f, "{}: {} children, child happiness {:.3} (on wishlist {:.2}%), gift happiness {:.3} (on list {:.2}%)",
+				s.name(), s.children, s.mean(s.child_happiness), s.on_list(&s.child_rank), s.mean(s.gift_happiness), s.on_list(&s.gift_rank))?;
+		}
+		let header = self.stats.iter().map(|s| format!("{:>12}", s.name())).collect::<Vec<_>>().join("");
+		for (title, f2) in &[("children by rank of their gift in their wishlist", (|s: &Stats| &s.child_rank) as fn(&Stats) -> &Vec<usize>),
+				("children by rank in the list of their gift", |s: &Stats| &s.gift_rank)] {
+			writeln!(f)?;
+			writeln!(f, "{}:", title)?;
+			writeln!(f, "{:>6}{}", "rank", header)?;
+			let len = f2(&self.stats[0]).len();
+			for r in 0..len {
+				if self.stats.iter().all(|s| f2(s)[r] == 0) { continue }
+				writeln!(f, "{:>6}{}", rank(r, len), self.stats.iter().map(|s| format!("{:>12}", f2(s)[r])).collect::<Vec<_>>().join(""))?;
+			}
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+
+/// An error in one of the input files. Lines and columns are 1-indexed.
+#[derive(Debug)]
+pub enum InputError {
+	Io { file: String, err: ::std::io::Error },
+	/// A field that is not a non-negative integer.
+	Parse { file: String, line: usize, column: usize, text: String },
+	/// A row with a wrong number of fields.
+	Ragged { file: String, line: usize, expected: String, found: usize },
+	/// The first line is not the expected header.
+	MissingHeader { file: String, expected: String, found: String },
+	/// Any other inconsistency, with the line if it is about a single line.
+	Invalid { file: String, line: Option<usize>, message: String },
+}
+
+impl InputError {
+	pub fn invalid<S: Into<String>>(file: &str, line: Option<usize>, message: S) -> InputError {
+		InputError::Invalid { file: file.to_string(), line, message: message.into() }
+	}
+}
+
+impl fmt::Display for InputError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			InputError::Io { ref file, ref err } => write!(f, "{}: {}", file, err),
+			InputError::Parse { ref file, line, column, ref text } => write!(f, "{}:{}: column {}: expected a non-negative integer, found {:?}", file, line, column, text),
+			InputError::Ragged { ref file, line, ref expected, found } => write!(f, "{}:{}: expected {} fields, found {}", file, line, expected, found),
+			InputError::MissingHeader { ref file, ref expected, ref found } => write!(f, "{}:1: expected header {:?}, found {:?}", file, expected, found),
+			InputError::Invalid { ref file, line: Some(line), ref message } => write!(f, "{}:{}: {}", file, line, message),
+			InputError::Invalid { ref file, line: None, ref message } => write!(f, "{}: {}", file, message),
+		}
+	}
+}
+
+impl ::std::error::Error for InputError {}
+
+/// Print the error and exit with status 1, for use in the binaries.
+pub trait OrExit<T> {
+	fn or_exit(self) -> T;
+}
+
+impl<T, E: fmt::Display> OrExit<T> for Result<T, E> {
+	fn or_exit(self) -> T {
+		match self {
+			Ok(v) => v,
+			Err(e) => {
+				eprintln!("error: {}", e);
+				::std::process::exit(1);
+			}
+		}
+	}
+}
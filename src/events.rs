@@ -0,0 +1,143 @@
+//! Progress events, written one per line to a sink (stderr unless set by init) if their level is at most the verbosity.
+//!
+//! An event has a name and a list of fields, and is written either as JSON, e.g. `{"t":1.25,"event":"update","bound":42}`,
+//! or as `t=1.25 event=update bound=42`, where t is the number of seconds since the first event or init.
+//! Levels: 0 for the final results, 1 for progress, 2 for every node, 3 for the internals of the flow solver.
+
+use std::fmt;
+use std::io::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+static VERBOSITY: AtomicUsize = AtomicUsize::new(1);
+static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+
+struct Sink {
+	out: Box<dyn Write + Send>,
+	json: bool,
+	start: Instant,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+	Int(i128),
+	Float(f64),
+	Str(String),
+	Bool(bool),
+	Null,
+}
+
+macro_rules! value_from_int {
+	($($t:ty),*) => { $(impl From<$t> for Value { fn from(v: $t) -> Value { Value::Int(v as i128) } })* };
+}
+value_from_int!(i32, i64, i128, u32, u64, usize);
+
+impl From<f64> for Value {
+	fn from(v: f64) -> Value {
+		Value::Float(v)
+	}
+}
+
+impl From<bool> for Value {
+	fn from(v: bool) -> Value {
+		Value::Bool(v)
+	}
+}
+
+impl<'a> From<&'a str> for Value {
+	fn from(v: &'a str) -> Value {
+		Value::Str(v.to_string())
+	}
+}
+
+impl From<String> for Value {
+	fn from(v: String) -> Value {
+		Value::Str(v)
+	}
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+	fn from(v: Option<T>) -> Value {
+		v.map_or(Value::Null, |v| v.into())
+	}
+}
+
+fn quote(s: &str) -> String {
+	let mut q = "\"".to_string();
+	for c in s.chars() {
+		match c {
+			'"' => q += "\\\"",
+			'\\' => q += "\\\\",
+			'\n' => q += "\\n",
+			c if (c as u32) < 0x20 => q += &format!("\\u{:04x}", c as u32),
+			c => q.push(c),
+		}
+	}
+	q + "\""
+}
+
+impl Value {
+	fn json(&self) -> String {
+		match *self {
+			Value::Float(v) if !v.is_finite() => "null".to_string(),
+			Value::Str(ref s) => quote(s),
+			Value::Null => "null".to_string(),
+			_ => self.to_string(),
+		}
+	}
+	fn text(&self) -> String {
+		match *self {
+			Value::Str(ref s) if s.is_empty() || s.contains([' ', '"', '=']) => quote(s),
+			_ => self.to_string(),
+		}
+	}
+}
+
+impl fmt::Display for Value {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Value::Int(v) => write!(f, "{}", v),
+			Value::Float(v) => write!(f, "{}", v),
+			Value::Str(ref s) => write!(f, "{}", s),
+			Value::Bool(v) => write!(f, "{}", v),
+			Value::Null => write!(f, "null"),
+		}
+	}
+}
+
+/// Write the events to `out`, as JSON lines if `json`, and keep those of level at most `verbosity`.
+pub fn init(out: Box<dyn Write + Send>, json: bool, verbosity: usize) {
+	VERBOSITY.store(verbosity, Ordering::Relaxed);
+	*SINK.lock().unwrap() = Some(Sink { out, json, start: Instant::now() });
+}
+
+pub fn enabled(level: usize) -> bool {
+	level <= VERBOSITY.load(Ordering::Relaxed)
+}
+
+/// Write an event regardless of its level; use the macro event! instead.
+pub fn emit(name: &str, fields: &[(&str, Value)]) {
+	let mut sink = SINK.lock().unwrap();
+	let sink = sink.get_or_insert_with(|| Sink { out: Box::new(std::io::stderr()), json: false, start: Instant::now() });
+	let t = Value::Float((sink.start.elapsed().as_secs_f64() * 1000.0).round() / 1000.0);
+	let all = [("t", t), ("event", Value::from(name))];
+	let fields = all.iter().chain(fields.iter());
+	let line = if sink.json {
+		format!("{{{}}}", fields.map(|&(k, ref v)| format!("{}:{}", quote(k), v.json())).collect::<Vec<_>>().join(","))
+	} else {
+		fields.map(|&(k, ref v)| format!("{}={}", k, v.text())).collect::<Vec<_>>().join(" ")
+	};
+	let _ = writeln!(sink.out, "{}", line);
+	let _ = sink.out.flush();
+}
+
+/// `event!(level, "name", key = value, ...)` emits the event if the verbosity is at least level.
+#[macro_export]
+macro_rules! event {
+	($level:expr, $name:expr $(, $k:ident = $v:expr)*) => {
+		if $crate::events::enabled($level) {
+			$crate::events::emit($name, &[$((stringify!($k), $crate::events::Value::from($v))),*]);
+		}
+	};
+}
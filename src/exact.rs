@@ -0,0 +1,280 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// Signed arbitrary precision integer, just enough for comparing scores without rounding.
+/// The magnitude is little endian in base 2^32 without leading zeros, and zero is not negative.
+#[derive(Clone, Debug, Default)]
+pub struct BigInt {
+	neg: bool,
+	mag: Vec<u32>,
+}
+
+fn trim(mut a: Vec<u32>) -> Vec<u32> {
+	while a.last() == Some(&0) {
+		a.pop();
+	}
+	a
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+	a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+	let mut c = Vec::with_capacity(a.len().max(b.len()) + 1);
+	let mut carry = 0u64;
+	for i in 0..a.len().max(b.len()) {
+		let s = carry + *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64;
+		c.push(s as u32);
+		carry = s >> 32;
+	}
+	c.push(carry as u32);
+	trim(c)
+}
+
+/// a - b for a >= b.
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+	let mut c = Vec::with_capacity(a.len());
+	let mut borrow = 0i64;
+	for i in 0..a.len() {
+		let mut s = a[i] as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+		borrow = 0;
+		if s < 0 {
+			s += 1 << 32;
+			borrow = 1;
+		}
+		c.push(s as u32);
+	}
+	trim(c)
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+	let mut c = vec![0u32; a.len() + b.len()];
+	for i in 0..a.len() {
+		let mut carry = 0u64;
+		for j in 0..b.len() {
+			let s = c[i + j] as u64 + a[i] as u64 * b[j] as u64 + carry;
+			c[i + j] = s as u32;
+			carry = s >> 32;
+		}
+		c[i + b.len()] = carry as u32;
+	}
+	trim(c)
+}
+
+impl BigInt {
+	fn new(neg: bool, mag: Vec<u32>) -> BigInt {
+		let mag = trim(mag);
+		BigInt { neg: neg && !mag.is_empty(), mag }
+	}
+	pub fn is_negative(&self) -> bool {
+		self.neg
+	}
+	pub fn is_zero(&self) -> bool {
+		self.mag.is_empty()
+	}
+	pub fn to_f64(&self) -> f64 {
+		let v = self.mag.iter().rev().fold(0.0, |v, &d| v * 4294967296.0 + d as f64);
+		if self.neg { -v } else { v }
+	}
+}
+
+impl From<i128> for BigInt {
+	fn from(v: i128) -> BigInt {
+		let mut u = v.unsigned_abs();
+		let mut mag = vec![];
+		while u > 0 {
+			mag.push(u as u32);
+			u >>= 32;
+		}
+		BigInt::new(v < 0, mag)
+	}
+}
+
+impl From<i64> for BigInt {
+	fn from(v: i64) -> BigInt {
+		BigInt::from(v as i128)
+	}
+}
+
+impl Neg for &BigInt {
+	type Output = BigInt;
+	fn neg(self) -> BigInt {
+		BigInt::new(!self.neg, self.mag.clone())
+	}
+}
+
+impl Add for &BigInt {
+	type Output = BigInt;
+	fn add(self, b: &BigInt) -> BigInt {
+		if self.neg == b.neg {
+			BigInt::new(self.neg, add_mag(&self.mag, &b.mag))
+		} else if cmp_mag(&self.mag, &b.mag) != Ordering::Less {
+			BigInt::new(self.neg, sub_mag(&self.mag, &b.mag))
+		} else {
+			BigInt::new(b.neg, sub_mag(&b.mag, &self.mag))
+		}
+	}
+}
+
+impl Sub for &BigInt {
+	type Output = BigInt;
+	fn sub(self, b: &BigInt) -> BigInt {
+		self + &-b
+	}
+}
+
+impl Mul for &BigInt {
+	type Output = BigInt;
+	fn mul(self, b: &BigInt) -> BigInt {
+		BigInt::new(self.neg != b.neg, mul_mag(&self.mag, &b.mag))
+	}
+}
+
+impl_cmp!(BigInt; |a, b| match (a.neg, b.neg) {
+	(false, true) => Ordering::Greater,
+	(true, false) => Ordering::Less,
+	(false, false) => cmp_mag(&a.mag, &b.mag),
+	(true, true) => cmp_mag(&b.mag, &a.mag),
+}; );
+
+impl fmt::Display for BigInt {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.mag.is_empty() {
+			return write!(f, "0");
+		}
+		// Repeatedly divide by 10^9.
+		let mut mag = self.mag.clone();
+		let mut digits = vec![];
+		while !mag.is_empty() {
+			let mut r = 0u64;
+			for d in mag.iter_mut().rev() {
+				let x = (r << 32) | *d as u64;
+				*d = (x / 1000000000) as u32;
+				r = x % 1000000000;
+			}
+			digits.push(r as u32);
+			mag = trim(mag);
+		}
+		if self.neg {
+			write!(f, "-")?;
+		}
+		write!(f, "{}", digits[digits.len() - 1])?;
+		for d in digits[..digits.len() - 1].iter().rev() {
+			write!(f, "{:09}", d)?;
+		}
+		Ok(())
+	}
+}
+
+/// The score num / den as an exact fraction with den > 0.
+#[derive(Clone, Debug)]
+pub struct ExactScore {
+	pub num: BigInt,
+	pub den: BigInt,
+}
+
+impl ExactScore {
+	pub fn to_f64(&self) -> f64 {
+		self.num.to_f64() / self.den.to_f64()
+	}
+}
+
+impl_cmp!(ExactScore; |a, b| (&a.num * &b.den).cmp(&(&b.num * &a.den)); );
+
+impl fmt::Display for ExactScore {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} / {}", self.num, self.den)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use {exact_score, get_score, ProblemSpec};
+
+	fn big(v: i128) -> BigInt {
+		BigInt::from(v)
+	}
+
+	/// Values around the limb boundaries, of both signs.
+	fn values() -> Vec<i128> {
+		let mut vs = vec![0, 1, 1000000000, 4294967295, 4294967296, (1 << 64) - 1, 1 << 64, (1 << 96) + 1, i64::min_value() as i128];
+		for v in vs.clone() {
+			vs.push(-v);
+		}
+		vs
+	}
+
+	#[test]
+	fn arithmetic() {
+		for &a in &values() {
+			for &b in &values() {
+				assert_eq!(&big(a) + &big(b), big(a + b), "{} + {}", a, b);
+				assert_eq!(&big(a) - &big(b), big(a - b), "{} - {}", a, b);
+				if let Some(c) = a.checked_mul(b) {
+					assert_eq!(&big(a) * &big(b), big(c), "{} * {}", a, b);
+				}
+				assert_eq!(big(a).cmp(&big(b)), a.cmp(&b), "{} <=> {}", a, b);
+			}
+		}
+	}
+
+	#[test]
+	fn carries_and_borrows() {
+		assert_eq!((&big(4294967295) + &big(1)).mag, vec![0, 1]);
+		assert_eq!((&big((1 << 96) - 1) + &big(1)).mag, vec![0, 0, 0, 1]);
+		assert_eq!((&big(1 << 96) - &big(1)).mag, vec![4294967295; 3]);
+		assert_eq!((&big(-(1 << 64)) + &big(1)).mag, vec![4294967295; 2]);
+		let x = &big(1 << 100) * &big(1 << 100);
+		assert_eq!(x.mag.len(), 7);
+		assert_eq!(x.mag[6], 1 << 8);
+	}
+
+	#[test]
+	fn zero_and_signs() {
+		let z = &big(-5) + &big(5);
+		assert!(z.is_zero() && !z.is_negative());
+		assert_eq!(z, BigInt::default());
+		assert!(!(-&BigInt::default()).is_negative());
+		assert!(!(&big(-3) * &big(0)).is_negative());
+		assert!((&big(-3) * &big(2)).is_negative());
+		assert!(!(&big(-3) * &big(-2)).is_negative());
+		assert!(big(-(1 << 40)) < big(-1));
+		assert!(big(-1) < big(0));
+	}
+
+	#[test]
+	fn display() {
+		assert_eq!(big(0).to_string(), "0");
+		assert_eq!(big(-7).to_string(), "-7");
+		assert_eq!(big(1000000000000000001).to_string(), "1000000000000000001");
+		assert_eq!(big(-1000000000).to_string(), "-1000000000");
+		assert_eq!(big(i128::max_value()).to_string(), i128::max_value().to_string());
+		let e20 = big(100000000000000000000);
+		assert_eq!((&e20 * &e20).to_string(), format!("1{}", "0".repeat(40)));
+	}
+
+	#[test]
+	fn score() {
+		let spec = ProblemSpec::competition();
+		let n = spec.children as i64;
+		let (cm, gm) = (spec.child_model(), spec.gift_model());
+		let (w1, w2) = (cm.max(spec.wishlist_len) - cm.missing(spec.wishlist_len), gm.max(spec.goodkids_len) - gm.missing(spec.goodkids_len));
+		let frac = |num: i128, den: i128| ExactScore { num: big(num), den: big(den) };
+		// Everyone gets their first choice.
+		assert_eq!(exact_score(&spec, n * w1, n * w2), frac(2, 1));
+		// Half the largest happiness on both sides.
+		assert_eq!(exact_score(&spec, n * (w1 + 1) / 2, n * (w2 + 1) / 2), frac(1, 4));
+		// Nobody gets a gift on their list, which is -1 / 200 and -1 / 2000 on average.
+		assert_eq!(exact_score(&spec, 0, 0), frac(-1001, 8000000000));
+		for &(s1, s2) in &[(0, 0), (n * w1, n * w2), (123456789, 987654321), (n * w1 - 1, 1), (1, n * w2 - 1)] {
+			let e = exact_score(&spec, s1, s2).to_f64();
+			let f = get_score(&spec, s1, s2);
+			assert!((e - f).abs() <= 1e-15 * f.abs().max(1.0), "{} {}: {} != {}", s1, s2, e, f);
+		}
+		assert!(exact_score(&spec, n * w1 - 1, n * w2) < exact_score(&spec, n * w1, n * w2));
+		assert_eq!(exact_score(&ProblemSpec::infer(0, 10, 10, 0), 0, 0), frac(0, 1));
+	}
+}
@@ -0,0 +1,92 @@
+/// Happiness of a child for the gift of a given rank in its wishlist, or of a gift for the child of a given rank in its list.
+/// The graphs store happiness(rank, len) - missing(len), so that an entry not on the list has weight 0.
+pub trait HappinessModel {
+	/// Happiness for the entry of rank `rank` (0-indexed) in a list of nominal length `len`.
+	fn happiness(&self, rank: usize, len: usize) -> i64;
+	/// Happiness for an entry not on the list.
+	fn missing(&self, len: usize) -> i64;
+	fn weight(&self, rank: usize, len: usize) -> i64 {
+		self.happiness(rank, len) - self.missing(len)
+	}
+	/// The largest possible happiness, used for the normalisation of the score.
+	fn max(&self, len: usize) -> i64 {
+		self.happiness(0, len)
+	}
+}
+
+/// 2 (len - rank), and -1 if not on the list.
+#[derive(Clone, Copy, Debug)]
+pub struct Competition;
+
+impl HappinessModel for Competition {
+	fn happiness(&self, rank: usize, len: usize) -> i64 {
+		2 * (len - rank) as i64
+	}
+	fn missing(&self, _: usize) -> i64 {
+		-1
+	}
+}
+
+/// len - rank, and 0 if not on the list.
+#[derive(Clone, Copy, Debug)]
+pub struct Linear;
+
+impl HappinessModel for Linear {
+	fn happiness(&self, rank: usize, len: usize) -> i64 {
+		(len - rank) as i64
+	}
+	fn missing(&self, _: usize) -> i64 {
+		0
+	}
+}
+
+/// round(top * ratio^rank), and 0 if not on the list.
+#[derive(Clone, Copy, Debug)]
+pub struct Geometric {
+	pub top: f64,
+	pub ratio: f64,
+}
+
+impl HappinessModel for Geometric {
+	fn happiness(&self, rank: usize, _: usize) -> i64 {
+		(self.top * self.ratio.powi(rank as i32)).round() as i64
+	}
+	fn missing(&self, _: usize) -> i64 {
+		0
+	}
+}
+
+/// table[rank], and missing if not on the list or rank >= table.len().
+#[derive(Clone, Debug)]
+pub struct Table {
+	pub table: Vec<i64>,
+	pub missing: i64,
+}
+
+impl HappinessModel for Table {
+	fn happiness(&self, rank: usize, _: usize) -> i64 {
+		if rank < self.table.len() { self.table[rank] } else { self.missing }
+	}
+	fn missing(&self, _: usize) -> i64 {
+		self.missing
+	}
+	fn max(&self, _: usize) -> i64 {
+		self.table.iter().cloned().max().unwrap_or(self.missing).max(self.missing)
+	}
+}
+
+/// Parse a model from one of
+/// `competition`, `linear`, `geometric <top> <ratio>` and `table <missing> <h0> <h1> ...`.
+pub fn parse(desc: &str) -> Result<Box<dyn HappinessModel>, String> {
+	let ws: Vec<&str> = desc.split_whitespace().collect();
+	let err = || format!("invalid happiness model: {:?}", desc);
+	let num = |i: usize| -> Result<f64, String> { ws.get(i).and_then(|w| w.parse().ok()).ok_or_else(&err) };
+	let int = |i: usize| -> Result<i64, String> { ws.get(i).and_then(|w| w.parse().ok()).ok_or_else(&err) };
+	Ok(match ws.first().cloned() {
+		Some("competition") => Box::new(Competition),
+		Some("linear") => Box::new(Linear),
+		Some("geometric") => Box::new(Geometric { top: num(1)?, ratio: num(2)? }),
+		Some("table") => Box::new(Table { missing: int(1)?, table: (2..ws.len()).map(int).collect::<Result<_, _>>()? }),
+		_ => return Err(err()),
+	})
+}
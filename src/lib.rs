@@ -1,100 +1,560 @@
 #[macro_use]
 pub mod common;
+#[macro_use]
+pub mod events;
 pub mod mincostcirculation;
+pub mod happiness;
+pub mod args;
+pub mod error;
+pub mod cache;
+pub mod preference;
+pub mod validate;
+pub mod exact;
+pub mod distribution;
+pub mod scorer;
 
 use std::io::BufRead;
 use std::io::Write;
+use std::borrow::Borrow;
+use happiness::HappinessModel;
+pub use error::{InputError, OrExit};
+pub use preference::PreferenceGraph;
+pub use exact::{BigInt, ExactScore};
+pub use scorer::Scorer;
+
+pub const CHILD_FILE: &str = "child_wishlist_v2.csv";
+pub const GIFT_FILE: &str = "gift_goodkids_v2.csv";
+pub const SPEC_FILE: &str = "spec.txt";
+pub const GROUP_FILE: &str = "groups.csv";
+pub const CAPACITY_FILE: &str = "gift_capacity.csv";
+pub const CACHE_FILE: &str = "graph.cache";
+
+/// Paths of the input files and of the cache, by default the names above in the working directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Files {
+	pub child: String,
+	pub gift: String,
+	pub spec: String,
+	pub groups: String,
+	pub capacity: String,
+	pub cache: String,
+}
+
+impl Default for Files {
+	fn default() -> Files {
+		Files::in_dir(".")
+	}
+}
+
+impl Files {
+	pub fn in_dir(dir: &str) -> Files {
+		let path = |f: &str| if dir == "." { f.to_string() } else { std::path::Path::new(dir).join(f).to_string_lossy().into_owned() };
+		Files {
+			child: path(CHILD_FILE),
+			gift: path(GIFT_FILE),
+			spec: path(SPEC_FILE),
+			groups: path(GROUP_FILE),
+			capacity: path(CAPACITY_FILE),
+			cache: path(CACHE_FILE),
+		}
+	}
+	/// The files in `--dir` (default .), each overridden by `--child`, `--gift`, `--spec`, `--groups`, `--capacity` and `--cache`.
+	pub fn from_args(args: &args::Args) -> Files {
+		let mut files = Files::in_dir(&args.get_or("dir", ".".to_string()));
+		let set = |name: &str, f: &mut String| if let Some(v) = args.get(name) { *f = v };
+		set("child", &mut files.child);
+		set("gift", &mut files.gift);
+		set("spec", &mut files.spec);
+		set("groups", &mut files.groups);
+		set("capacity", &mut files.capacity);
+		set("cache", &mut files.cache);
+		files
+	}
+}
+
+/// Shape of an instance.
+/// Without GROUP_FILE, children 0..3*triplets are triplets, the following 2*twins children are twins, and the rest are singles.
+/// Without CAPACITY_FILE, every gift has gift_capacity.
+/// A list may be shorter than wishlist_len/goodkids_len, and the happiness of its entries is the same as in a list of full length.
+/// child_happiness and gift_happiness describe the happiness models (see happiness::parse).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProblemSpec {
+	pub children: usize,
+	pub gifts: usize,
+	pub gift_capacity: usize,
+	pub triplets: usize,
+	pub twins: usize,
+	pub wishlist_len: usize,
+	pub goodkids_len: usize,
+	pub child_happiness: String,
+	pub gift_happiness: String,
+}
+
+impl ProblemSpec {
+	/// The Kaggle 2017 instance.
+	pub fn competition() -> ProblemSpec {
+		ProblemSpec {
+			children: 1000000,
+			gifts: 1000,
+			gift_capacity: 1000,
+			triplets: 1667,
+			twins: 20000,
+			wishlist_len: 100,
+			goodkids_len: 1000,
+			child_happiness: "competition".to_string(),
+			gift_happiness: "competition".to_string(),
+		}
+	}
+	/// Infer the shape from the numbers of rows and the longest rows of the two CSVs.
+	/// The numbers of triplets and twins follow the competition rule (0.5% and 4% of the children),
+	/// and the capacity is the smallest that fits every child.
+	pub fn infer(children: usize, gifts: usize, wishlist_len: usize, goodkids_len: usize) -> ProblemSpec {
+		ProblemSpec {
+			children,
+			gifts,
+			gift_capacity: if gifts > 0 { children.div_ceil(gifts) } else { 0 },
+			triplets: (children * 5 / 1000).div_ceil(3),
+			twins: children / 50,
+			wishlist_len,
+			goodkids_len,
+			child_happiness: "competition".to_string(),
+			gift_happiness: "competition".to_string(),
+		}
+	}
+	/// Read `key = value` lines. Missing keys are taken from base.
+	pub fn read(file: &str, base: ProblemSpec) -> Result<ProblemSpec, InputError> {
+		let mut spec = base;
+		for (l, line) in open(file)?.lines().enumerate() {
+			let line = line.map_err(|e| io_error(file, e))?;
+			let line = line.split('#').next().unwrap().trim();
+			if line.is_empty() { continue }
+			let mut kv = line.splitn(2, '=');
+			let key = kv.next().unwrap().trim();
+			let value = match kv.next() {
+				Some(v) => v.trim(),
+				None => return Err(InputError::invalid(file, Some(l + 1), format!("expected `key = value`, found {:?}", line))),
+			};
+			let num = || parse_field(file, l + 1, 2, value);
+			match key {
+				"children" => spec.children = num()?,
+				"gifts" => spec.gifts = num()?,
+				"gift_capacity" => spec.gift_capacity = num()?,
+				"triplets" => spec.triplets = num()?,
+				"twins" => spec.twins = num()?,
+				"wishlist_len" => spec.wishlist_len = num()?,
+				"goodkids_len" => spec.goodkids_len = num()?,
+				"child_happiness" | "gift_happiness" => {
+					happiness::parse(value).map_err(|e| InputError::invalid(file, Some(l + 1), e))?;
+					if key == "child_happiness" {
+						spec.child_happiness = value.to_string();
+					} else {
+						spec.gift_happiness = value.to_string();
+					}
+				}
+				_ => return Err(InputError::invalid(file, Some(l + 1), format!("unknown key {:?}", key))),
+			}
+		}
+		Ok(spec)
+	}
+	pub fn child_model(&self) -> Box<dyn HappinessModel> {
+		happiness::parse(&self.child_happiness).unwrap()
+	}
+	pub fn gift_model(&self) -> Box<dyn HappinessModel> {
+		happiness::parse(&self.gift_happiness).unwrap()
+	}
+}
+
+fn io_error(file: &str, err: std::io::Error) -> InputError {
+	InputError::Io { file: file.to_string(), err }
+}
 
-pub const N: usize = 1000000;
-pub const N3: usize = 1667;
-pub const N2: usize = 20000;
+fn open(file: &str) -> Result<std::io::BufReader<std::fs::File>, InputError> {
+	std::fs::File::open(file).map(std::io::BufReader::new).map_err(|e| io_error(file, e))
+}
 
-pub fn read_csv(file: &str) -> Vec<Vec<usize>> {
+fn parse_field(file: &str, line: usize, column: usize, s: &str) -> Result<usize, InputError> {
+	s.trim().parse().map_err(|_| InputError::Parse { file: file.to_string(), line, column, text: s.to_string() })
+}
+
+/// Read rows of the form `i,x1,x2,...` where i is the row index, and return the x's.
+/// If width is given, every row must have that many fields including i.
+pub fn read_csv(file: &str, width: Option<usize>) -> Result<Vec<Vec<usize>>, InputError> {
 	let mut list = vec![];
-	let reader = std::io::BufReader::new(std::fs::File::open(file).unwrap());
-	for line in reader.lines() {
+	for (l, line) in open(file)?.lines().enumerate() {
+		let line = line.map_err(|e| io_error(file, e))?;
 		let mut tmp = vec![];
-		for s in line.unwrap().split(',') {
-			tmp.push(s.parse().unwrap());
+		for (c, s) in line.split(',').enumerate() {
+			tmp.push(parse_field(file, l + 1, c + 1, s)?);
+		}
+		if let Some(w) = width {
+			if tmp.len() != w {
+				return Err(InputError::Ragged { file: file.to_string(), line: l + 1, expected: w.to_string(), found: tmp.len() });
+			}
+		}
+		if tmp[0] != l {
+			return Err(InputError::invalid(file, Some(l + 1), format!("expected ID {}, found {}", l, tmp[0])));
 		}
 		tmp.remove(0);
 		list.push(tmp);
 	}
-	list
+	Ok(list)
+}
+
+/// Same as read_csv, but the rows are concatenated into one list, with offset[i]..offset[i + 1] being row i.
+pub fn read_lists(file: &str) -> Result<(Vec<usize>, Vec<u32>), InputError> {
+	let mut offset = vec![0];
+	let mut list = vec![];
+	for (l, line) in open(file)?.lines().enumerate() {
+		let line = line.map_err(|e| io_error(file, e))?;
+		for (c, s) in line.split(',').enumerate() {
+			let x = parse_field(file, l + 1, c + 1, s)?;
+			if c == 0 {
+				if x != l {
+					return Err(InputError::invalid(file, Some(l + 1), format!("expected ID {}, found {}", l, x)));
+				}
+			} else if x > u32::MAX as usize {
+				return Err(InputError::invalid(file, Some(l + 1), format!("column {}: {} is out of range", c + 1, x)));
+			} else {
+				list.push(x as u32);
+			}
+		}
+		offset.push(list.len());
+	}
+	Ok((offset, list))
 }
 
+/// Write through a temporary file, so that `file` always holds a complete solution even if the process is killed.
 pub fn write_solution(ps: &Vec<usize>, file: &str) {
-	let mut writer = std::io::BufWriter::new(std::fs::File::create(file).unwrap());
+	let tmp = format!("{}.tmp", file);
+	let mut writer = std::io::BufWriter::new(std::fs::File::create(&tmp).unwrap());
 	writeln!(writer, "ChildId,GiftId").unwrap();
 	for i in 0..ps.len() {
 		writeln!(writer, "{},{}", i, ps[i]).unwrap();
 	}
+	writer.flush().unwrap();
+	drop(writer);
+	std::fs::rename(&tmp, file).unwrap();
 }
 
-pub fn read_solution(file: &str) -> Vec<usize> {
+/// Read a submission whose rows must be in the order of ChildId.
+pub fn read_solution(file: &str) -> Result<Vec<usize>, InputError> {
 	let mut list = vec![];
-	let mut reader = std::io::BufReader::new(std::fs::File::open(file).unwrap());
-	reader.read_line(&mut String::new()).unwrap();
-	for line in reader.lines() {
-		let s = line.unwrap();
-		list.push(s.split(',').nth(1).unwrap().parse().unwrap());
+	for (line, i, j) in read_solution_rows(file)? {
+		if i != list.len() {
+			return Err(InputError::invalid(file, Some(line), format!("expected ChildId {}, found {}", list.len(), i)));
+		}
+		list.push(j);
+	}
+	Ok(list)
+}
+
+/// Read a submission as it is, as a list of (line, ChildId, GiftId).
+pub fn read_solution_rows(file: &str) -> Result<Vec<(usize, usize, usize)>, InputError> {
+	let mut list = vec![];
+	let mut lines = open(file)?.lines();
+	let header = match lines.next() {
+		Some(h) => h.map_err(|e| io_error(file, e))?,
+		None => String::new(),
+	};
+	if header.trim() != "ChildId,GiftId" {
+		return Err(InputError::MissingHeader { file: file.to_string(), expected: "ChildId,GiftId".to_string(), found: header });
+	}
+	for (l, line) in lines.enumerate() {
+		let s = line.map_err(|e| io_error(file, e))?;
+		let fs: Vec<&str> = s.split(',').collect();
+		if fs.len() != 2 {
+			return Err(InputError::Ragged { file: file.to_string(), line: l + 2, expected: "2".to_string(), found: fs.len() });
+		}
+		let i = parse_field(file, l + 2, 1, fs[0])?;
+		let j = parse_field(file, l + 2, 2, fs[1])?;
+		list.push((l + 2, i, j));
+	}
+	Ok(list)
+}
+
+/// Children that must receive the same gift.
+/// A unit is either a group or a single child; groups come first, followed by the singles in increasing order.
+#[derive(Clone, Debug)]
+pub struct Groups {
+	pub members: Vec<Vec<usize>>,
+	pub singles: Vec<usize>,
+	unit_of: Vec<usize>,
+}
+
+impl Groups {
+	/// Build from the groups of two or more children. Every other child is a single.
+	pub fn new(n: usize, members: Vec<Vec<usize>>) -> Groups {
+		let mut unit_of = vec![!0; n];
+		for (u, ms) in members.iter().enumerate() {
+			assert!(ms.len() >= 2);
+			for &i in ms {
+				assert!(i < n, "child {} is out of range", i);
+				assert!(unit_of[i] == !0, "child {} belongs to two groups", i);
+				unit_of[i] = u;
+			}
+		}
+		let mut singles = vec![];
+		for i in 0..n {
+			if unit_of[i] == !0 {
+				unit_of[i] = members.len() + singles.len();
+				singles.push(i);
+			}
+		}
+		Groups { members, singles, unit_of }
+	}
+	/// Triplets and twins laid out as described in ProblemSpec.
+	pub fn contiguous(spec: &ProblemSpec) -> Groups {
+		let mut members = vec![];
+		for i in 0..spec.triplets {
+			members.push(vec![i * 3, i * 3 + 1, i * 3 + 2]);
+		}
+		for i in 0..spec.twins {
+			members.push(vec![spec.triplets * 3 + i * 2, spec.triplets * 3 + i * 2 + 1]);
+		}
+		Groups::new(spec.children, members)
+	}
+	/// One group per line, listing its child IDs separated by commas. Groups of one child are singles.
+	pub fn read(spec: &ProblemSpec, file: &str) -> Result<Groups, InputError> {
+		let mut members = vec![];
+		let mut seen = vec![0; spec.children];
+		for (l, line) in open(file)?.lines().enumerate() {
+			let line = line.map_err(|e| io_error(file, e))?;
+			if line.trim().is_empty() { continue }
+			let mut ms = vec![];
+			for (c, s) in line.split(',').enumerate() {
+				let i = parse_field(file, l + 1, c + 1, s)?;
+				if i >= spec.children {
+					return Err(InputError::invalid(file, Some(l + 1), format!("column {}: child {} is out of range", c + 1, i)));
+				}
+				if seen[i] != 0 {
+					return Err(InputError::invalid(file, Some(l + 1), format!("column {}: child {} is already in the group on line {}", c + 1, i, seen[i])));
+				}
+				seen[i] = l + 1;
+				ms.push(i);
+			}
+			if ms.len() >= 2 {
+				members.push(ms);
+			}
+		}
+		Ok(Groups::new(spec.children, members))
+	}
+	pub fn units(&self) -> usize {
+		self.members.len() + self.singles.len()
+	}
+	pub fn unit(&self, u: usize) -> &[usize] {
+		if u < self.members.len() { &self.members[u] } else { std::slice::from_ref(&self.singles[u - self.members.len()]) }
+	}
+	pub fn unit_of(&self, i: usize) -> usize {
+		self.unit_of[i]
+	}
+	/// Expand a gift per unit to a gift per child.
+	pub fn expand(&self, ps: &Vec<usize>) -> Vec<usize> {
+		let mut qs = vec![!0; self.unit_of.len()];
+		for u in 0..self.units() {
+			for &i in self.unit(u) {
+				qs[i] = ps[u];
+			}
+		}
+		qs
+	}
+}
+
+/// Read the group file if it exists, and use the contiguous layout otherwise.
+pub fn load_groups(spec: &ProblemSpec, files: &Files) -> Result<Groups, InputError> {
+	if std::path::Path::new(&files.groups).exists() {
+		return Groups::read(spec, &files.groups);
+	}
+	if spec.triplets * 3 + spec.twins * 2 > spec.children {
+		return Err(InputError::invalid(&files.spec, None, "more children in triplets and twins than children"));
+	}
+	Ok(Groups::contiguous(spec))
+}
+
+/// Read the capacity file (lines of GiftId,Capacity) if it exists, and use gift_capacity otherwise.
+/// The total capacity must fit every child.
+pub fn load_capacities(spec: &ProblemSpec, files: &Files) -> Result<Vec<usize>, InputError> {
+	let (file, cap) = if std::path::Path::new(&files.capacity).exists() {
+		let cs = read_csv(&files.capacity, Some(2))?;
+		if cs.len() != spec.gifts {
+			return Err(InputError::invalid(&files.capacity, None, format!("expected {} gifts, found {}", spec.gifts, cs.len())));
+		}
+		(&files.capacity, cs.into_iter().map(|c| c[0]).collect())
+	} else {
+		(&files.spec, vec![spec.gift_capacity; spec.gifts])
+	};
+	let total: usize = cap.iter().sum();
+	if total < spec.children {
+		return Err(InputError::invalid(file, None, format!("total capacity {} is less than the {} children", total, spec.children)));
+	}
+	Ok(cap)
+}
+
+/// Build the child->gift and gift->child preference lists indexed by child.
+/// They are loaded from the cache if it was built from the current CSVs and spec file,
+/// and otherwise built by build_graph and written to the cache.
+pub fn construct_graph(files: &Files) -> Result<(ProblemSpec, PreferenceGraph, PreferenceGraph), InputError> {
+	let sum = cache::checksum(&[&files.child, &files.gift, &files.spec])?;
+	if let Some(res) = cache::read(&files.cache, sum) {
+		return Ok(res);
+	}
+	let (spec, g, g2) = build_graph(files)?;
+	eprintln!("writing {}", files.cache);
+	if let Err(e) = cache::write(&files.cache, sum, &spec, &g, &g2) {
+		eprintln!("{}: {}", files.cache, e);
 	}
-	list
+	Ok((spec, g, g2))
 }
 
-pub fn construct_graph() -> (Vec<Vec<(usize, i64)>>, Vec<Vec<(usize, i64)>>) {
-	let cs = read_csv("child_wishlist_v2.csv");
-	let gs = read_csv("gift_goodkids_v2.csv");
-	let mut g = vec![vec![]; N];
-	for i in 0..N {
-		for j in 0..100 {
-			g[i].push((cs[i][j], 2 * (100 - j as i64) + 1));
+/// Read the two CSVs and build the preference lists.
+/// The shape is inferred from the CSVs and overridden by the spec file if it exists.
+pub fn build_graph(files: &Files) -> Result<(ProblemSpec, PreferenceGraph, PreferenceGraph), InputError> {
+	let (co, cs) = read_lists(&files.child)?;
+	let (go, gs) = read_lists(&files.gift)?;
+	let longest = |o: &Vec<usize>| (1..o.len()).map(|i| o[i] - o[i - 1]).max().unwrap_or(0);
+	let mut spec = ProblemSpec::infer(co.len() - 1, go.len() - 1, longest(&co), longest(&go));
+	if std::path::Path::new(&files.spec).exists() {
+		spec = ProblemSpec::read(&files.spec, spec)?;
+	}
+	if co.len() - 1 != spec.children {
+		return Err(InputError::invalid(&files.child, None, format!("expected {} children, found {}", spec.children, co.len() - 1)));
+	}
+	if go.len() - 1 != spec.gifts {
+		return Err(InputError::invalid(&files.gift, None, format!("expected {} gifts, found {}", spec.gifts, go.len() - 1)));
+	}
+	let n = spec.children;
+	let (cm, gm) = (spec.child_model(), spec.gift_model());
+	let ragged = |file: &str, i: usize, len: usize, found: usize| {
+		InputError::Ragged { file: file.to_string(), line: i + 1, expected: format!("at most {}", len + 1), found: found + 1 }
+	};
+	let out_of_range = |file: &str, i: usize, j: usize, x: u32| InputError::invalid(file, Some(i + 1), format!("column {}: {} is out of range", j + 2, x));
+	let weight = |m: &dyn HappinessModel, rank: usize, len: usize| {
+		let w = m.weight(rank, len);
+		if w as i32 as i64 != w {
+			return Err(InputError::invalid(&files.spec, None, format!("happiness {} does not fit in 32 bits", w)));
+		}
+		Ok(w as i32)
+	};
+	let mut cw = Vec::with_capacity(cs.len());
+	for i in 0..n {
+		if co[i + 1] - co[i] > spec.wishlist_len {
+			return Err(ragged(&files.child, i, spec.wishlist_len, co[i + 1] - co[i]));
+		}
+		for k in co[i]..co[i + 1] {
+			if cs[k] as usize >= spec.gifts {
+				return Err(out_of_range(&files.child, i, k - co[i], cs[k]));
+			}
+			cw.push(weight(&*cm, k - co[i], spec.wishlist_len)?);
 		}
-		g[i].sort();
 	}
-	let mut g2 = vec![vec![]; N];
-	for i in 0..1000 {
-		for j in 0..1000 {
-			g2[gs[i][j]].push((i, 2 * (1000 - j as i64) + 1));
+	let g = PreferenceGraph::new(co, cs, cw);
+	for i in 0..n {
+		let row = &g.to[g.offset[i]..g.offset[i + 1]];
+		if let Some(w) = row.windows(2).find(|w| w[0] == w[1]) {
+			return Err(InputError::invalid(&files.child, Some(i + 1), format!("gift {} appears twice", w[0])));
 		}
 	}
-	for i in 0..N {
-		g2[i].sort();
+	let mut offset = vec![0; n + 1];
+	for i in 0..spec.gifts {
+		if go[i + 1] - go[i] > spec.goodkids_len {
+			return Err(ragged(&files.gift, i, spec.goodkids_len, go[i + 1] - go[i]));
+		}
+		for k in go[i]..go[i + 1] {
+			if gs[k] as usize >= n {
+				return Err(out_of_range(&files.gift, i, k - go[i], gs[k]));
+			}
+			offset[gs[k] as usize + 1] += 1;
+		}
+	}
+	for i in 0..n {
+		offset[i + 1] += offset[i];
 	}
-	(g, g2)
+	let mut pos = offset.clone();
+	let mut to = vec![0; gs.len()];
+	let mut gw = vec![0; gs.len()];
+	for i in 0..spec.gifts {
+		for k in go[i]..go[i + 1] {
+			let c = gs[k] as usize;
+			to[pos[c]] = i as u32;
+			gw[pos[c]] = weight(&*gm, k - go[i], spec.goodkids_len)?;
+			pos[c] += 1;
+		}
+	}
+	let g2 = PreferenceGraph::new(offset, to, gw);
+	for i in 0..n {
+		let row = &g2.to[g2.offset[i]..g2.offset[i + 1]];
+		if let Some(w) = row.windows(2).find(|w| w[0] == w[1]) {
+			return Err(InputError::invalid(&files.gift, Some(w[0] as usize + 1), format!("child {} appears twice", i)));
+		}
+	}
+	Ok((spec, g, g2))
 }
 
-pub fn merge<T: std::ops::Add<Output = T> + Copy>(a: &Vec<(usize, T)>, b: &Vec<(usize, T)>) -> Vec<(usize, T)> {
+/// Merge two lists of (gift, weight) sorted by gift, adding the weights of the common gifts.
+pub fn merge<T, A, B>(a: A, b: B) -> Vec<(usize, T)>
+		where T: std::ops::Add<Output = T> + Copy, A: IntoIterator, A::Item: Borrow<(usize, T)>, B: IntoIterator, B::Item: Borrow<(usize, T)> {
 	let mut c = vec![];
-	let mut i = 0;
-	let mut j = 0;
-	while i < a.len() && j < b.len() {
-		if a[i].0 == b[j].0 {
-			c.push((a[i].0, a[i].1 + b[j].1));
-			i += 1;
-			j += 1;
-		} else if a[i].0 < b[j].0 {
-			c.push(a[i]);
-			i += 1;
+	let mut a = a.into_iter().map(|e| *e.borrow()).peekable();
+	let mut b = b.into_iter().map(|e| *e.borrow()).peekable();
+	while let (Some(&x), Some(&y)) = (a.peek(), b.peek()) {
+		if x.0 == y.0 {
+			c.push((x.0, x.1 + y.1));
+			a.next();
+			b.next();
+		} else if x.0 < y.0 {
+			c.push(x);
+			a.next();
 		} else {
-			c.push(b[j]);
-			j += 1;
+			c.push(y);
+			b.next();
 		}
 	}
-	c.extend(a[i..].iter());
-	c.extend(b[j..].iter());
+	c.extend(a);
+	c.extend(b);
 	c
 }
 
-pub fn get_cost<T: Copy>(a: &Vec<(usize, T)>, i: usize) -> Option<T> {
-	for &(j, w) in a {
+pub fn get_cost<T: Copy, A: IntoIterator>(a: A, i: usize) -> Option<T> where A::Item: Borrow<(usize, T)> {
+	for e in a {
+		let (j, w) = *e.borrow();
 		if j == i {
 			return Some(w);
 		}
 	}
 	None
 }
+/// Competition metric from the sums of the weights of construct_graph.
+/// Each sum is shifted back by the happiness of the entries not on the list and normalised by the largest possible happiness.
+pub fn get_score(spec: &ProblemSpec, score1: i64, score2: i64) -> f64 {
+	let n = spec.children as i64;
+	let (cm, gm) = (spec.child_model(), spec.gift_model());
+	let score1 = score1 + n * cm.missing(spec.wishlist_len);
+	let score2 = score2 + n * gm.missing(spec.goodkids_len);
+	let max1 = (n * cm.max(spec.wishlist_len)) as f64;
+	let max2 = (n * gm.max(spec.goodkids_len)) as f64;
+	(score1 as f64 / max1).powf(3.0) + (score2 as f64 / max2).powf(3.0)
+}
 
-pub fn get_score(score1: i64, score2: i64) -> f64 {
-	((score1 - 1000000) as f64 / 200000000.0).powf(3.0) + ((score2 - 1000000) as f64 / 2000000000.0).powf(3.0)
+/// Greatest common divisor, non-negative.
+pub fn gcd(a: i128, b: i128) -> i128 {
+	if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+/// Same as get_score, but exact, and 0 without children.
+/// With the normalisers d1, d2 and l = lcm(d1, d2), the score is ((s1 l / d1)^3 + (s2 l / d2)^3) / l^3.
+pub fn exact_score(spec: &ProblemSpec, score1: i64, score2: i64) -> ExactScore {
+	let n = spec.children as i128;
+	if n == 0 {
+		return ExactScore { num: BigInt::from(0i64), den: BigInt::from(1i64) };
+	}
+	let (cm, gm) = (spec.child_model(), spec.gift_model());
+	let score1 = score1 as i128 + n * cm.missing(spec.wishlist_len) as i128;
+	let score2 = score2 as i128 + n * gm.missing(spec.goodkids_len) as i128;
+	let d1 = n * cm.max(spec.wishlist_len) as i128;
+	let d2 = n * gm.max(spec.goodkids_len) as i128;
+	let l = d1 / gcd(d1, d2) * d2;
+	let (x, y) = (score1 * (l / d1), score2 * (l / d2));
+	let g = gcd(gcd(x, y), l);
+	let (x, y, l) = (BigInt::from(x / g), BigInt::from(y / g), BigInt::from(l / g));
+	let cube = |a: &BigInt| &(a * a) * a;
+	ExactScore { num: &cube(&x) + &cube(&y), den: cube(&l) }
 }
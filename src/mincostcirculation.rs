@@ -67,7 +67,7 @@ impl<C, W> Graph<C, W> where	C: Copy + Default + Ord + Sub<Output = C> + Neg<Out
 		let mut visit = vec![false; n];
 		let mut ok = false;
 		'refine: while { eps /= 2.into(); eps > W::default() } {
-			eprintln!("{:?}", eps);
+			event!(3, "eps", eps = format!("{:?}", eps));
 			for v in 0..n {
 				for i in 0..self.es[v].len() {
 					let e = self.es[v][i];
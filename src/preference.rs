@@ -0,0 +1,69 @@
+/// Preference lists in compressed sparse row form.
+/// Row i holds the pairs (gift, weight) of child i sorted by gift.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PreferenceGraph {
+	pub offset: Vec<usize>,
+	pub to: Vec<u32>,
+	pub weight: Vec<i32>,
+}
+
+impl PreferenceGraph {
+	/// Build from the concatenated rows, sorting each row by gift.
+	pub fn new(offset: Vec<usize>, to: Vec<u32>, weight: Vec<i32>) -> PreferenceGraph {
+		assert!(!offset.is_empty() && offset[offset.len() - 1] == to.len() && to.len() == weight.len());
+		let mut g = PreferenceGraph { offset, to, weight };
+		let mut tmp = vec![];
+		for i in 0..g.rows() {
+			let (s, t) = (g.offset[i], g.offset[i + 1]);
+			if g.to[s..t].windows(2).all(|w| w[0] <= w[1]) { continue }
+			tmp.clear();
+			tmp.extend((s..t).map(|k| (g.to[k], g.weight[k])));
+			tmp.sort();
+			for (k, &(j, w)) in (s..t).zip(tmp.iter()) {
+				g.to[k] = j;
+				g.weight[k] = w;
+			}
+		}
+		g
+	}
+	pub fn rows(&self) -> usize {
+		self.offset.len() - 1
+	}
+	pub fn row(&self, i: usize) -> Row<'_> {
+		let (s, t) = (self.offset[i], self.offset[i + 1]);
+		Row { to: &self.to[s..t], weight: &self.weight[s..t] }
+	}
+	/// The weight of (i, j), by binary search in row i.
+	pub fn get(&self, i: usize, j: usize) -> Option<i64> {
+		if j > u32::MAX as usize {
+			return None;
+		}
+		let (s, t) = (self.offset[i], self.offset[i + 1]);
+		self.to[s..t].binary_search(&(j as u32)).ok().map(|k| self.weight[s + k] as i64)
+	}
+}
+
+/// Iterator over the pairs (gift, weight) of a row.
+#[derive(Clone, Debug)]
+pub struct Row<'a> {
+	to: &'a [u32],
+	weight: &'a [i32],
+}
+
+impl<'a> Iterator for Row<'a> {
+	type Item = (usize, i64);
+	fn next(&mut self) -> Option<(usize, i64)> {
+		if self.to.is_empty() {
+			return None;
+		}
+		let e = (self.to[0] as usize, self.weight[0] as i64);
+		self.to = &self.to[1..];
+		self.weight = &self.weight[1..];
+		Some(e)
+	}
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		(self.to.len(), Some(self.to.len()))
+	}
+}
+
+impl<'a> ExactSizeIterator for Row<'a> {}
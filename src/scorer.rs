@@ -0,0 +1,226 @@
+//! A solution with its happiness sums, for evaluating and applying moves of units between gifts.
+
+use super::*;
+
+/// Constant time lookup of (child, gift) in a PreferenceGraph: an open-addressed table per row, with linear probing,
+/// whose slots hold 1 + the position of an entry in the row (0 for an empty slot).
+/// A row of d entries gets the smallest power of two above 5 d / 4 slots of one byte, about 128 MB for the wishlists
+/// of the competition instance. Rows of 255 entries or more have no table and are searched by binary search.
+#[derive(Clone, Debug)]
+struct Index {
+	/// The slots of row i are start[i]..start[i + 1].
+	start: Vec<usize>,
+	slots: Vec<u8>,
+}
+
+fn hash(j: usize, size: usize) -> usize {
+	((j as u32).wrapping_mul(0x9e3779b1) >> (32 - size.trailing_zeros())) as usize
+}
+
+impl Index {
+	fn new(g: &PreferenceGraph) -> Index {
+		let mut start = vec![0];
+		let mut slots = vec![];
+		for i in 0..g.rows() {
+			let d = g.row(i).len();
+			if d > 0 && d < 255 {
+				let (s, size) = (slots.len(), (d + d / 4 + 1).next_power_of_two());
+				slots.resize(s + size, 0);
+				for (k, (j, _)) in g.row(i).enumerate() {
+					let mut h = hash(j, size);
+					while slots[s + h] != 0 {
+						h = (h + 1) & (size - 1);
+					}
+					slots[s + h] = k as u8 + 1;
+				}
+			}
+			start.push(slots.len());
+		}
+		Index { start, slots }
+	}
+	fn get(&self, g: &PreferenceGraph, i: usize, j: usize) -> i64 {
+		let (s, size) = (self.start[i], self.start[i + 1] - self.start[i]);
+		if size == 0 {
+			return g.get(i, j).unwrap_or(0);
+		}
+		let mut h = hash(j, size);
+		loop {
+			let p = self.slots[s + h];
+			if p == 0 {
+				return 0;
+			}
+			let k = g.offset[i] + p as usize - 1;
+			if g.to[k] as usize == j {
+				return g.weight[k] as i64;
+			}
+			h = (h + 1) & (size - 1);
+		}
+	}
+}
+
+/// Change of the two sums of weights.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Delta {
+	pub child: i64,
+	pub gift: i64,
+}
+
+/// The current assignment of a solution, with the number of children per gift and the two sums of weights.
+/// A move is a list of (unit, gift) giving each listed unit a new gift; the units of a move must be distinct.
+pub struct Scorer<'a> {
+	pub spec: &'a ProblemSpec,
+	pub groups: &'a Groups,
+	g1: &'a PreferenceGraph,
+	g2: &'a PreferenceGraph,
+	idx1: Index,
+	idx2: Index,
+	ps: Vec<usize>,
+	count: Vec<usize>,
+	score1: i64,
+	score2: i64,
+}
+
+impl<'a> Scorer<'a> {
+	/// ps is the gift of each child, where gifts >= spec.gifts (e.g. !0) mean no gift.
+	pub fn new(spec: &'a ProblemSpec, groups: &'a Groups, g1: &'a PreferenceGraph, g2: &'a PreferenceGraph, ps: Vec<usize>) -> Scorer<'a> {
+		assert!(ps.len() == spec.children);
+		let mut s = Scorer {
+			spec,
+			groups,
+			g1,
+			g2,
+			idx1: Index::new(g1),
+			idx2: Index::new(g2),
+			ps,
+			count: vec![0; spec.gifts],
+			score1: 0,
+			score2: 0,
+		};
+		for i in 0..spec.children {
+			let j = s.ps[i];
+			if j < spec.gifts {
+				s.count[j] += 1;
+				let (a, b) = s.weight(i, j);
+				s.score1 += a;
+				s.score2 += b;
+			}
+		}
+		s
+	}
+	/// The weights of construct_graph for (child, gift), 0 if not on the list.
+	pub fn weight(&self, i: usize, j: usize) -> (i64, i64) {
+		if j >= self.spec.gifts {
+			return (0, 0);
+		}
+		(self.idx1.get(self.g1, i, j), self.idx2.get(self.g2, i, j))
+	}
+	pub fn assignment(&self) -> &Vec<usize> {
+		&self.ps
+	}
+	pub fn gift(&self, i: usize) -> usize {
+		self.ps[i]
+	}
+	/// The gift of the first member of unit u.
+	pub fn unit_gift(&self, u: usize) -> usize {
+		self.ps[self.groups.unit(u)[0]]
+	}
+	/// Number of children given gift j.
+	pub fn count(&self, j: usize) -> usize {
+		self.count[j]
+	}
+	pub fn sums(&self) -> (i64, i64) {
+		(self.score1, self.score2)
+	}
+	pub fn score(&self) -> f64 {
+		get_score(self.spec, self.score1, self.score2)
+	}
+	pub fn exact(&self) -> ExactScore {
+		exact_score(self.spec, self.score1, self.score2)
+	}
+	pub fn delta(&self, mv: &[(usize, usize)]) -> Delta {
+		let mut d = Delta::default();
+		for &(u, j) in mv {
+			for &i in self.groups.unit(u) {
+				let (a, b) = self.weight(i, self.ps[i]);
+				let (c, e) = self.weight(i, j);
+				d.child += c - a;
+				d.gift += e - b;
+			}
+		}
+		d
+	}
+	/// Give unit u gift j.
+	pub fn move_delta(&self, u: usize, j: usize) -> Delta {
+		self.delta(&[(u, j)])
+	}
+	/// Exchange the gifts of units u and v.
+	pub fn swap_delta(&self, u: usize, v: usize) -> Delta {
+		self.delta(&[(u, self.unit_gift(v)), (v, self.unit_gift(u))])
+	}
+	/// Whether no gift exceeds its capacity after the move.
+	pub fn fits(&self, mv: &[(usize, usize)], cap: &Vec<usize>) -> bool {
+		let mut change: Vec<(usize, i64)> = vec![];
+		for &(u, j) in mv {
+			for &i in self.groups.unit(u) {
+				if self.ps[i] < self.spec.gifts {
+					change.push((self.ps[i], -1));
+				}
+				if j < self.spec.gifts {
+					change.push((j, 1));
+				}
+			}
+		}
+		change.sort();
+		let mut k = 0;
+		while k < change.len() {
+			let j = change[k].0;
+			let mut c = self.count[j] as i64;
+			while k < change.len() && change[k].0 == j {
+				c += change[k].1;
+				k += 1;
+			}
+			if c > cap[j] as i64 {
+				return false;
+			}
+		}
+		true
+	}
+	/// The exact score after a move of the given delta.
+	pub fn exact_after(&self, d: Delta) -> ExactScore {
+		exact_score(self.spec, self.score1 + d.child, self.score2 + d.gift)
+	}
+	/// Whether a move of the given delta strictly increases the score, compared exactly.
+	/// The score is increasing in both sums, so the exact comparison is needed only when their changes have opposite signs.
+	pub fn improves(&self, d: Delta) -> bool {
+		if d.child >= 0 && d.gift >= 0 {
+			d.child > 0 || d.gift > 0
+		} else if d.child <= 0 && d.gift <= 0 {
+			false
+		} else {
+			self.exact_after(d) > self.exact()
+		}
+	}
+	pub fn apply(&mut self, mv: &[(usize, usize)]) {
+		let d = self.delta(mv);
+		self.score1 += d.child;
+		self.score2 += d.gift;
+		for &(u, j) in mv {
+			for &i in self.groups.unit(u) {
+				if self.ps[i] < self.spec.gifts {
+					self.count[self.ps[i]] -= 1;
+				}
+				if j < self.spec.gifts {
+					self.count[j] += 1;
+				}
+				self.ps[i] = j;
+			}
+		}
+	}
+	pub fn apply_move(&mut self, u: usize, j: usize) {
+		self.apply(&[(u, j)]);
+	}
+	pub fn apply_swap(&mut self, u: usize, v: usize) {
+		let (a, b) = (self.unit_gift(u), self.unit_gift(v));
+		self.apply(&[(u, b), (v, a)]);
+	}
+}
@@ -0,0 +1,105 @@
+use super::*;
+use std::fmt;
+
+/// Everything wrong with a submission. Lines refer to the submission file.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+	/// Children without a row.
+	pub missing: Vec<usize>,
+	/// (line, ChildId) of the rows repeating an earlier ChildId. The earlier row is used.
+	pub duplicate: Vec<(usize, usize)>,
+	/// (line, ChildId) of the rows with ChildId >= children.
+	pub unknown_child: Vec<(usize, usize)>,
+	/// (line, ChildId, GiftId) of the rows with GiftId >= gifts.
+	pub unknown_gift: Vec<(usize, usize, usize)>,
+	/// Number of children without a valid gift.
+	pub unassigned: usize,
+	/// (gift, count, capacity) of the gifts given to more children than their capacity.
+	pub over: Vec<(usize, usize, usize)>,
+	/// (gift, count, capacity) of the gifts given to fewer children than their capacity.
+	pub under: Vec<(usize, usize, usize)>,
+	/// (group, [(child, gift)]) of the groups whose members got different gifts.
+	pub split: Vec<(usize, Vec<(usize, usize)>)>,
+}
+
+impl Report {
+	/// Whether the submission is feasible. Gifts under capacity are fine as long as every child has a gift.
+	pub fn is_ok(&self) -> bool {
+		self.unassigned == 0 && self.duplicate.is_empty() && self.unknown_child.is_empty() && self.over.is_empty() && self.split.is_empty()
+	}
+}
+
+/// Check the rows read by read_solution_rows, and return the report and the gift of each child (!0 if none).
+pub fn validate(spec: &ProblemSpec, groups: &Groups, cap: &Vec<usize>, rows: &Vec<(usize, usize, usize)>) -> (Report, Vec<usize>) {
+	let n = spec.children;
+	let mut report = Report::default();
+	let mut ps = vec![!0; n];
+	let mut seen = vec![false; n];
+	for &(line, i, j) in rows {
+		if i >= n {
+			report.unknown_child.push((line, i));
+		} else if seen[i] {
+			report.duplicate.push((line, i));
+		} else {
+			seen[i] = true;
+			if j >= spec.gifts {
+				report.unknown_gift.push((line, i, j));
+			} else {
+				ps[i] = j;
+			}
+		}
+	}
+	report.missing = (0..n).filter(|&i| !seen[i]).collect();
+	report.unassigned = ps.iter().filter(|&&j| j == !0).count();
+	let mut count = vec![0; spec.gifts];
+	for i in 0..n {
+		if ps[i] != !0 {
+			count[ps[i]] += 1;
+		}
+	}
+	for j in 0..spec.gifts {
+		if count[j] > cap[j] {
+			report.over.push((j, count[j], cap[j]));
+		} else if count[j] < cap[j] {
+			report.under.push((j, count[j], cap[j]));
+		}
+	}
+	for (u, ms) in groups.members.iter().enumerate() {
+		if ms.iter().any(|&i| ps[i] != ps[ms[0]]) {
+			report.split.push((u, ms.iter().map(|&i| (i, ps[i])).collect()));
+		}
+	}
+	(report, ps)
+}
+
+fn gift(j: usize) -> String {
+	if j == !0 { "-".to_string() } else { j.to_string() }
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "unassigned children: {}", self.unassigned)?;
+		if !self.missing.is_empty() {
+			writeln!(f, "missing ChildId: {}", self.missing.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(" "))?;
+		}
+		for &(line, i) in &self.duplicate {
+			writeln!(f, "line {}: duplicate ChildId {}", line, i)?;
+		}
+		for &(line, i) in &self.unknown_child {
+			writeln!(f, "line {}: ChildId {} is out of range", line, i)?;
+		}
+		for &(line, i, j) in &self.unknown_gift {
+			writeln!(f, "line {}: ChildId {}: GiftId {} is out of range", line, i, j)?;
+		}
+		for &(j, c, k) in &self.over {
+			writeln!(f, "gift {}: {} children > capacity {}", j, c, k)?;
+		}
+		for &(j, c, k) in &self.under {
+			writeln!(f, "gift {}: {} children < capacity {}", j, c, k)?;
+		}
+		for &(u, ref ms) in &self.split {
+			writeln!(f, "group {} is split: {}", u, ms.iter().map(|&(i, j)| format!("{}->{}", i, gift(j))).collect::<Vec<_>>().join(" "))?;
+		}
+		write!(f, "{}", if self.is_ok() { "valid" } else { "invalid" })
+	}
+}
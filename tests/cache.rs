@@ -0,0 +1,41 @@
+//! Writes a cache, damages it, and checks that it is rejected instead of read.
+
+extern crate santa17;
+
+use santa17::*;
+
+#[test]
+fn damaged_cache() {
+	let spec = ProblemSpec::infer(3, 2, 2, 2);
+	let g = PreferenceGraph::new(vec![0, 2, 3, 3], vec![1, 0, 1], vec![4, 2, 4]);
+	let g2 = PreferenceGraph::new(vec![0, 1, 1, 3], vec![0, 0, 1], vec![4, 4, 2]);
+	let file = std::env::temp_dir().join(format!("santa17-cache-{}", std::process::id()));
+	let file = file.to_str().unwrap();
+	cache::write(file, 7, &spec, &g, &g2).unwrap();
+	assert!(!std::path::Path::new(&format!("{}.tmp", file)).exists());
+	assert_eq!(cache::read(file, 7), Some((spec.clone(), g.clone(), g2.clone())));
+	assert_eq!(cache::read(file, 8), None);
+	let bytes = std::fs::read(file).unwrap();
+	// The header of g starts after the magic, version, checksum, 7 sizes and the two happiness names.
+	let at = 20 + 7 * 8 + 2 * (8 + "competition".len());
+	let damage = |k: usize, b: &[u8]| {
+		let mut bytes = bytes.clone();
+		bytes[k..k + b.len()].copy_from_slice(b);
+		std::fs::write(file, bytes).unwrap();
+		cache::read(file, 7)
+	};
+	// Number of rows and of entries larger than the file.
+	assert_eq!(damage(at, &u64::max_value().to_le_bytes()), None);
+	assert_eq!(damage(at + 8, &(1u64 << 40).to_le_bytes()), None);
+	// Decreasing offsets, and offsets not ending at the number of entries.
+	assert_eq!(damage(at + 16 + 8, &5u64.to_le_bytes()), None);
+	assert_eq!(damage(at + 16 + 24, &2u64.to_le_bytes()), None);
+	// A gift out of range.
+	assert_eq!(damage(at + 16 + 32, &9u32.to_le_bytes()), None);
+	// A string longer than the file.
+	assert_eq!(damage(20 + 7 * 8, &u64::max_value().to_le_bytes()), None);
+	// Truncated.
+	std::fs::write(file, &bytes[..bytes.len() - 1]).unwrap();
+	assert_eq!(cache::read(file, 7), None);
+	let _ = std::fs::remove_file(file);
+}
@@ -0,0 +1,54 @@
+//! Runs gen and checks its output.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Run gen into a fresh directory and return the directory.
+fn gen(name: &str, args: &[&str]) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("santa17-gen-{}-{}", name, std::process::id()));
+	let _ = std::fs::remove_dir_all(&dir);
+	std::fs::create_dir_all(&dir).unwrap();
+	let status = Command::new(env!("CARGO_BIN_EXE_gen")).arg("--out").arg(&dir).args(args).status().unwrap();
+	assert!(status.success(), "gen {:?} failed", args);
+	dir
+}
+
+/// The files written by gen, by name.
+fn files(dir: &PathBuf) -> Vec<(String, Vec<u8>)> {
+	let mut fs: Vec<_> = std::fs::read_dir(dir).unwrap().map(|e| {
+		let e = e.unwrap();
+		(e.file_name().to_string_lossy().to_string(), std::fs::read(e.path()).unwrap())
+	}).collect();
+	fs.sort();
+	fs
+}
+
+/// The same seed gives the same files, and another seed other wishlists.
+#[test]
+fn same_seed_same_output() {
+	let args = ["--children", "500", "--gifts", "20", "--skew", "1", "--stock-spread", "0.5", "--groups", "4:5,3:10,2:20"];
+	let a = gen("a", &[&args[..], &["--seed", "3"]].concat());
+	let b = gen("b", &[&args[..], &["--seed", "3"]].concat());
+	let c = gen("c", &[&args[..], &["--seed", "4"]].concat());
+	let (fa, fb, fc) = (files(&a), files(&b), files(&c));
+	assert_eq!(fa.iter().map(|f| f.0.as_str()).collect::<Vec<_>>(),
+		["child_wishlist_v2.csv", "gift_capacity.csv", "gift_goodkids_v2.csv", "groups.csv", "spec.txt"]);
+	assert!(fa == fb, "the same seed gives different files");
+	assert!(fa[0] != fc[0], "another seed gives the same wishlists");
+	for dir in &[a, b, c] {
+		let _ = std::fs::remove_dir_all(dir);
+	}
+}
+
+/// Invalid values are reported with the option and exit with status 1.
+#[test]
+fn invalid_options() {
+	for args in &[&["--gifts", "0"][..], &["--children", "10", "--groups", "3:4"], &["--groups", "1:3"], &["--groups", "3:x"]] {
+		let dir = std::env::temp_dir().join(format!("santa17-gen-invalid-{}", std::process::id()));
+		let out = Command::new(env!("CARGO_BIN_EXE_gen")).arg("--out").arg(&dir).args(*args).output().unwrap();
+		let text = String::from_utf8_lossy(&out.stderr);
+		assert_eq!(out.status.code(), Some(1), "gen {:?}:\n{}", args, text);
+		assert!(text.starts_with(&format!("error: invalid value for {}", args[args.len() - 2])), "gen {:?}:\n{}", args, text);
+		let _ = std::fs::remove_dir_all(&dir);
+	}
+}
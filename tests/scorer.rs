@@ -0,0 +1,85 @@
+//! Applies random moves to a Scorer and compares its state with a recomputation from the assignment.
+
+extern crate rand;
+extern crate santa17;
+
+use rand::{Isaac64Rng, Rng, SeedableRng};
+use santa17::*;
+
+/// Random rows of up to len distinct gifts out of m for each of n children, with weights in 1..=100.
+fn graph(rng: &mut Isaac64Rng, n: usize, m: usize, len: usize) -> PreferenceGraph {
+	let (mut offset, mut to, mut weight) = (vec![0], vec![], vec![]);
+	for _ in 0..n {
+		let mut js: Vec<u32> = (0..m as u32).collect();
+		rng.shuffle(&mut js);
+		for &j in &js[..rng.gen_range(0, len + 1)] {
+			to.push(j);
+			weight.push(rng.gen_range(1, 101));
+		}
+		offset.push(to.len());
+	}
+	PreferenceGraph::new(offset, to, weight)
+}
+
+/// The sums of weights and the number of children of each gift, from the assignment alone.
+fn recompute(spec: &ProblemSpec, g1: &PreferenceGraph, g2: &PreferenceGraph, ps: &Vec<usize>) -> ((i64, i64), Vec<usize>) {
+	let mut sums = (0, 0);
+	let mut count = vec![0; spec.gifts];
+	for (i, &j) in ps.iter().enumerate() {
+		if j >= spec.gifts { continue }
+		count[j] += 1;
+		sums.0 += g1.row(i).find(|e| e.0 == j).map_or(0, |e| e.1);
+		sums.1 += g2.row(i).find(|e| e.0 == j).map_or(0, |e| e.1);
+	}
+	(sums, count)
+}
+
+#[test]
+fn random_moves() {
+	// Wishlists short enough for the index of Scorer, and long enough for some to be searched instead.
+	for &(first, last, m, len) in &[(0, 20, 7, 4), (20, 22, 300, 280)] {
+		let mut spec = ProblemSpec::infer(60, m, len, 12);
+		spec.triplets = 3;
+		spec.twins = 6;
+		let groups = Groups::contiguous(&spec);
+		for seed in first..last {
+			let mut rng = Isaac64Rng::from_seed(&[seed][..]);
+			let g1 = graph(&mut rng, spec.children, spec.gifts, spec.wishlist_len);
+			let g2 = graph(&mut rng, spec.children, spec.gifts, 5);
+			let cap: Vec<usize> = (0..spec.gifts).map(|_| rng.gen_range(5, 15)).collect();
+			// No gift one time in eight.
+			let random_gift = |rng: &mut Isaac64Rng| if rng.gen_range(0, 8) == 0 { !0 } else { rng.gen_range(0, spec.gifts) };
+			let mut gifts = vec![0; groups.units()];
+			for u in 0..groups.units() {
+				gifts[u] = random_gift(&mut rng);
+			}
+			let mut s = Scorer::new(&spec, &groups, &g1, &g2, groups.expand(&gifts));
+			for _ in 0..500 {
+				let mut mv = vec![];
+				for _ in 0..rng.gen_range(1, 4) {
+					let u = rng.gen_range(0, groups.units());
+					if mv.iter().all(|&(v, _)| v != u) {
+						mv.push((u, random_gift(&mut rng)));
+					}
+				}
+				let (before, _) = recompute(&spec, &g1, &g2, s.assignment());
+				let d = s.delta(&mv);
+				let fits = s.fits(&mv, &cap);
+				// fits only looks at the gifts the move takes from or gives to someone.
+				let mut touched: Vec<usize> = mv.iter().flat_map(|&(u, j)| groups.unit(u).iter().map(|&i| s.gift(i)).chain(Some(j)).collect::<Vec<_>>()).collect();
+				touched.retain(|&j| j < spec.gifts);
+				s.apply(&mv);
+				let (sums, count) = recompute(&spec, &g1, &g2, s.assignment());
+				assert_eq!(s.sums(), sums, "seed {} move {:?}", seed, mv);
+				assert_eq!((d.child, d.gift), (sums.0 - before.0, sums.1 - before.1), "seed {} move {:?}", seed, mv);
+				assert_eq!(fits, touched.iter().all(|&j| count[j] <= cap[j]), "seed {} move {:?}", seed, mv);
+				for j in 0..spec.gifts {
+					assert_eq!(s.count(j), count[j]);
+				}
+				for &(u, j) in &mv {
+					assert!(groups.unit(u).iter().all(|&i| s.gift(i) == j));
+				}
+			}
+		}
+	}
+}
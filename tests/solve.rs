@@ -0,0 +1,151 @@
+//! Runs gen, solve and score on small generated instances.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A fresh directory holding the instance written by gen with the given options.
+fn instance(name: &str, args: &[&str]) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("santa17-{}-{}", name, std::process::id()));
+	let _ = std::fs::remove_dir_all(&dir);
+	std::fs::create_dir_all(&dir).unwrap();
+	let status = Command::new(env!("CARGO_BIN_EXE_gen")).arg("--out").arg(&dir).args(args).status().unwrap();
+	assert!(status.success(), "gen {:?} failed", args);
+	dir
+}
+
+/// Run solve in dir and return the fields of its summary event.
+fn solve(dir: &PathBuf, args: &[&str]) -> HashMap<String, String> {
+	let out = Command::new(env!("CARGO_BIN_EXE_solve")).current_dir(dir).args(&["--verbose", "0"]).args(args).output().unwrap();
+	let text = String::from_utf8_lossy(&out.stderr);
+	assert!(out.status.success(), "solve {:?} failed:\n{}", args, text);
+	let line = text.lines().find(|l| l.contains("event=summary")).unwrap_or_else(|| panic!("solve {:?} gave no summary:\n{}", args, text));
+	line.split(' ').filter_map(|kv| {
+		let mut kv = kv.splitn(2, '=');
+		Some((kv.next()?.to_string(), kv.next()?.to_string()))
+	}).collect()
+}
+
+/// The sums of weights printed by score for the solution in dir, which must be valid.
+fn score(dir: &PathBuf, file: &str) -> String {
+	let out = Command::new(env!("CARGO_BIN_EXE_score")).current_dir(dir).arg(file).output().unwrap();
+	let text = String::from_utf8_lossy(&out.stdout).to_string();
+	assert!(out.status.success(), "score {} failed:\n{}", file, text);
+	let lines: Vec<&str> = text.lines().collect();
+	assert!(lines.contains(&"valid"), "{} is not valid:\n{}", file, text);
+	lines[lines.iter().position(|&l| l == "valid").unwrap() + 1].to_string()
+}
+
+/// Solve to optimality and return the objective and the sums of the solution written to file.
+fn optimum(dir: &PathBuf, file: &str, args: &[&str]) -> (String, String) {
+	let s = solve(dir, &[&["--out", file][..], args].concat());
+	assert_eq!(s["optimal"], "true", "solve {:?} did not finish", args);
+	(s["incumbent"].clone(), score(dir, file))
+}
+
+/// With a tight incumbent, reduce fixes most units and leaves gifts with less room than the groups assigned to them in part.
+#[test]
+fn warm_start_from_optimum() {
+	for &(gifts, seed) in &[("60", "1"), ("20", "12")] {
+		let dir = instance(&format!("init-{}-{}", gifts, seed), &["--children", "600", "--gifts", gifts, "--seed", seed, "--stock-spread", "0.5"]);
+		let best = optimum(&dir, "a.csv", &[]);
+		for &rule in &["strong", "top", "pseudocost", "reliability"] {
+			assert_eq!(optimum(&dir, "b.csv", &["--init", "a.csv", "--branching", rule]), best);
+		}
+		let lb = (best.0.parse::<i128>().unwrap() - 1).to_string();
+		assert_eq!(optimum(&dir, "c.csv", &["--lb", &lb]), best);
+		let _ = std::fs::remove_dir_all(&dir);
+	}
+}
+
+/// An instance whose search takes a few dozen nodes.
+fn search_instance(name: &str) -> PathBuf {
+	instance(name, &["--children", "800", "--gifts", "80", "--wishlist-len", "5", "--goodkids-len", "50", "--seed", "2", "--stock-spread", "0.5"])
+}
+
+/// A search stopped every few nodes and resumed from its checkpoint reaches the same optimum,
+/// and every resumed search writes the incumbent to its own output.
+#[test]
+fn checkpoint_and_resume() {
+	let dir = search_instance("resume");
+	let best = optimum(&dir, "a.csv", &[]);
+	let mut limit = 0;
+	loop {
+		limit += 5;
+		let (nodes, out) = (limit.to_string(), format!("b{}.csv", limit));
+		let mut args = vec!["--out", &out, "--checkpoint", "ck", "--nodes", &nodes];
+		if limit > 5 {
+			args.push("--resume");
+		}
+		let s = solve(&dir, &args);
+		if s["saved"] == "true" {
+			score(&dir, &out);
+		}
+		if s["optimal"] == "true" {
+			assert_eq!((s["incumbent"].clone(), score(&dir, &out)), best);
+			assert!(!dir.join("ck").exists(), "the checkpoint is not removed");
+			break;
+		}
+		assert!(dir.join("ck").exists(), "no checkpoint after {} nodes", limit);
+		assert!(limit < 1000, "the search does not finish");
+	}
+	assert!(limit > 5, "the search is never stopped");
+	let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Every node selection reaches the same optimum.
+#[test]
+fn node_selection() {
+	let dir = search_instance("selection");
+	let best = optimum(&dir, "a.csv", &[]);
+	for &selection in &["best", "hybrid"] {
+		assert_eq!(optimum(&dir, "b.csv", &["--node-selection", selection]), best, "{}", selection);
+	}
+	let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// The search on several threads reaches the optimum of the search on one.
+#[test]
+fn threads() {
+	let dir = search_instance("threads");
+	let best = optimum(&dir, "a.csv", &["--threads", "1"]);
+	for &selection in &["dfs", "best", "hybrid"] {
+		assert_eq!(optimum(&dir, "b.csv", &["--threads", "4", "--node-selection", selection]), best, "{}", selection);
+	}
+	let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Every branching rule reaches the same optimum with every node selection.
+#[test]
+fn branching_rules() {
+	let dir = search_instance("branching");
+	let best = optimum(&dir, "a.csv", &[]);
+	for &selection in &["dfs", "best", "hybrid"] {
+		for &rule in &["strong", "top", "pseudocost", "reliability"] {
+			assert_eq!(optimum(&dir, "b.csv", &["--node-selection", selection, "--branching", rule]), best, "{} {}", selection, rule);
+		}
+	}
+	let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// A damaged checkpoint is reported as an error instead of making the resumed search panic.
+#[test]
+fn damaged_checkpoint() {
+	let dir = search_instance("damaged");
+	let s = solve(&dir, &["--checkpoint", "ck", "--nodes", "2"]);
+	assert_eq!(s["optimal"], "false");
+	let bytes = std::fs::read(dir.join("ck")).unwrap();
+	let mut errors = 0;
+	for k in 0..24 {
+		let at = 12 + (bytes.len() - 20) * k / 24;
+		let mut b = bytes.clone();
+		b[at..at + 8].copy_from_slice(&(1u64 << 62).to_le_bytes());
+		std::fs::write(dir.join("ck2"), &b).unwrap();
+		let out = Command::new(env!("CARGO_BIN_EXE_solve")).current_dir(&dir).args(&["--verbose", "0", "--checkpoint", "ck2", "--resume"]).output().unwrap();
+		let text = String::from_utf8_lossy(&out.stderr);
+		assert!(out.status.code() == Some(0) || out.status.code() == Some(1), "damage at {}:\n{}", at, text);
+		errors += (out.status.code() == Some(1)) as usize;
+	}
+	assert!(errors > 0);
+	let _ = std::fs::remove_dir_all(&dir);
+}